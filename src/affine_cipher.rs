@@ -0,0 +1,287 @@
+use std::fmt;
+use std::io;
+
+use rand::{CryptoRng, Rng, RngCore};
+
+use crate::alphabet::{Alphabet, ASCII_LETTERS};
+use crate::candidate::{self, Candidate};
+use crate::cipher::Cipher;
+use crate::detection::ngram::{Fitness, NgramScorer};
+use crate::error::{Error, Result};
+use crate::keygen::GenerateKey;
+use crate::stream::{CharTransform, StreamingCipher, TextTransform};
+
+/// `E(x) = a·x + b` modulo the alphabet length. Caesar is `a = 1` and Atbash
+/// is `a = b = len - 1`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AffineKey {
+    pub a: usize,
+    pub b: usize,
+}
+
+impl AffineKey {
+    pub fn new(a: usize, b: usize) -> Self {
+        Self { a, b }
+    }
+}
+
+impl fmt::Display for AffineKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{},{}", self.a, self.b)
+    }
+}
+
+pub fn encode(message: &str, key: AffineKey) -> Result<String> {
+    encode_with(message, key, &ASCII_LETTERS)
+}
+
+
+pub fn decode(message: &str, key: AffineKey) -> Result<String> {
+    decode_with(message, key, &ASCII_LETTERS)
+}
+
+
+/// Fails unless `key.a` is coprime to the alphabet length, as otherwise two
+/// letters would encode to the same one.
+pub fn encode_with(message: &str, key: AffineKey, alphabet: &Alphabet) -> Result<String> {
+    let key = check_key(key, alphabet)?;
+    Ok(handle(message, key.a, key.b, alphabet))
+}
+
+
+pub fn decode_with(message: &str, key: AffineKey, alphabet: &Alphabet) -> Result<String> {
+    let (a, b) = inverse(key, alphabet)?;
+    Ok(handle(message, a, b, alphabet))
+}
+
+
+/// The `x` with `a·x = 1 (mod m)`, if `a` and `m` are coprime. `None` for a
+/// modulus below 2.
+pub fn modular_inverse(a: usize, m: usize) -> Option<usize> {
+    if m < 2 {
+        return None;
+    }
+    // extended euclid, tracking only the coefficient of a
+    let (mut r0, mut r1) = (m as i64, (a % m) as i64);
+    let (mut t0, mut t1) = (0i64, 1i64);
+    while r1 != 0 {
+        let q = r0 / r1;
+        (r0, r1) = (r1, r0 - q * r1);
+        (t0, t1) = (t1, t0 - q * t1);
+    }
+    if r0 != 1 {
+        return None;
+    }
+    Some(t0.rem_euclid(m as i64) as usize)
+}
+
+
+/// Tries all 312 keys over the ASCII letters (12 values of `a` coprime to 26
+/// times 26 values of `b`) and ranks the decryptions by n-gram fitness, best
+/// first. Works without spaces or dictionary words.
+pub fn crack(message: &str) -> Result<Vec<Candidate<AffineKey>>> {
+    let letters = ASCII_LETTERS.indices(message);
+    if letters.is_empty() {
+        return Err(Error::InvalidArgument("message has no letters to analyse".to_string()));
+    }
+    // very short messages are scored on shorter n-grams
+    let scorer = NgramScorer::english(letters.len().min(4))?;
+    let size = ASCII_LETTERS.len();
+
+    let mut plain = vec![0; letters.len()];
+    let mut candidates = Vec::with_capacity(312);
+    for a in (1..size).filter(|&a| modular_inverse(a, size).is_some()) {
+        for b in 0..size {
+            let key = AffineKey::new(a, b);
+            let (inverse_a, inverse_b) = inverse(key, &ASCII_LETTERS)?;
+            for (plain, &letter) in plain.iter_mut().zip(&letters) {
+                *plain = (inverse_a * letter + inverse_b) % size;
+            }
+            let score = scorer.fitness(&plain);
+            candidates.push(Candidate::new(key, handle(message, inverse_a, inverse_b, &ASCII_LETTERS), score));
+        }
+    }
+    candidate::rank(&mut candidates);
+    Ok(candidates)
+}
+
+// the key reduced modulo the alphabet length, so `a·x + b` cannot overflow
+fn check_key(key: AffineKey, alphabet: &Alphabet) -> Result<AffineKey> {
+    let len = alphabet.len();
+    if modular_inverse(key.a, len).is_none() {
+        return Err(Error::InvalidKey(format!(
+            "affine key a = {} must be coprime to the alphabet length {}", key.a, len
+        )));
+    }
+    Ok(AffineKey::new(key.a % len, key.b % len))
+}
+
+// D(y) = a⁻¹·(y - b) = a⁻¹·y + a⁻¹·(len - b), so decoding is another affine map
+fn inverse(key: AffineKey, alphabet: &Alphabet) -> Result<(usize, usize)> {
+    let key = check_key(key, alphabet)?;
+    let len = alphabet.len();
+    let a = modular_inverse(key.a, len).expect("checked above");
+    Ok((a, a * (len - key.b) % len))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Affine {
+    key: AffineKey,
+    alphabet: Alphabet,
+}
+
+impl Affine {
+    pub fn new(key: AffineKey) -> Result<Self> {
+        Self::with_alphabet(key, ASCII_LETTERS.clone())
+    }
+
+    /// The key is stored reduced modulo the alphabet length.
+    pub fn with_alphabet(key: AffineKey, alphabet: Alphabet) -> Result<Self> {
+        let key = check_key(key, &alphabet)?;
+        Ok(Self { key, alphabet })
+    }
+}
+
+impl Cipher for Affine {
+    type Key = AffineKey;
+
+    fn validate_key(key: &AffineKey) -> Result<()> {
+        check_key(*key, &ASCII_LETTERS).map(|_| ())
+    }
+
+    fn key(&self) -> &AffineKey {
+        &self.key
+    }
+
+    fn encrypt(&self, message: &str) -> Result<String> {
+        encode_with(message, self.key, &self.alphabet)
+    }
+
+    fn decrypt(&self, message: &str) -> Result<String> {
+        decode_with(message, self.key, &self.alphabet)
+    }
+}
+
+impl StreamingCipher for Affine {
+    type Encryptor = TextTransform<AffineStream>;
+    type Decryptor = TextTransform<AffineStream>;
+
+    fn encryptor(&self) -> Self::Encryptor {
+        TextTransform::new(AffineStream { a: self.key.a, b: self.key.b, alphabet: self.alphabet.clone() })
+    }
+
+    fn decryptor(&self) -> Self::Decryptor {
+        let (a, b) = inverse(self.key, &self.alphabet).expect("validated key");
+        TextTransform::new(AffineStream { a, b, alphabet: self.alphabet.clone() })
+    }
+}
+
+pub struct AffineStream {
+    a: usize,
+    b: usize,
+    alphabet: Alphabet,
+}
+
+impl CharTransform for AffineStream {
+    fn transform(&mut self, ch: char, output: &mut String) -> io::Result<()> {
+        output.push(map(ch, self.a, self.b, &self.alphabet));
+        Ok(())
+    }
+}
+
+impl GenerateKey for Affine {
+    // a = 1 would make it a plain caesar shift
+    fn generate_key<R: RngCore + CryptoRng + ?Sized>(rng: &mut R, _length: usize) -> AffineKey {
+        let size = ASCII_LETTERS.len();
+        let coprime: Vec<usize> = (2..size).filter(|&a| modular_inverse(a, size).is_some()).collect();
+        AffineKey::new(coprime[rng.random_range(0..coprime.len())], rng.random_range(0..size))
+    }
+}
+
+fn map(ch: char, a: usize, b: usize, alphabet: &Alphabet) -> char {
+    match alphabet.index_of(ch) {
+        Some(x) => alphabet.symbol_like((a * x + b) % alphabet.len(), ch),
+        None => ch,
+    }
+}
+
+fn handle(message: &str, a: usize, b: usize, alphabet: &Alphabet) -> String {
+    message.chars().
+        map(|ch| map(ch, a, b, alphabet)).
+        collect()
+}
+
+
+#[cfg(test)]
+mod test {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    use super::*;
+
+    #[test]
+    fn test_encode() {
+        let key = AffineKey::new(5, 8);
+        assert_eq!("Ihhwvc Swfrcp", encode("Affine Cipher", key).expect("valid key"));
+        assert_eq!("Affine Cipher", decode("Ihhwvc Swfrcp", key).expect("valid key"));
+
+        // caesar and atbash are special cases
+        assert_eq!(crate::caeser_cipher::encode("Hello", 3), encode("Hello", AffineKey::new(1, 3)).expect("valid key"));
+        assert_eq!(crate::atbash_cipher::encode("Hello"), encode("Hello", AffineKey::new(25, 25)).expect("valid key"));
+
+        assert!(matches!(encode("Hello", AffineKey::new(13, 1)), Err(Error::InvalidKey(_))));
+        assert!(Affine::new(AffineKey::new(4, 0)).is_err());
+    }
+
+    #[test]
+    fn test_modular_inverse() {
+        assert_eq!(Some(21), modular_inverse(5, 26));
+        assert_eq!(Some(25), modular_inverse(25, 26));
+        assert_eq!(Some(1), modular_inverse(1, 26));
+        assert_eq!(None, modular_inverse(13, 26));
+        assert_eq!(None, modular_inverse(0, 26));
+        assert_eq!(None, modular_inverse(5, 0));
+        assert_eq!(None, modular_inverse(5, 1));
+        assert_eq!(12, (1..26).filter(|&a| modular_inverse(a, 26).is_some()).count());
+    }
+
+    #[test]
+    fn test_alphabets() {
+        let cyrillic = Alphabet::cyrillic();
+        assert!(encode_with("Привет", AffineKey::new(3, 1), &cyrillic).is_err());
+        let key = AffineKey::new(4, 7);
+        let encoded = encode_with("Привет, мир!", key, &cyrillic).expect("4 is coprime to 33");
+        assert_eq!("Привет, мир!", decode_with(&encoded, key, &cyrillic).expect("valid key"));
+    }
+
+    #[test]
+    fn test_crack() {
+        let message = "Meet me under the old clock tower at midnight";
+        let candidates = crack(&encode(message, AffineKey::new(7, 19)).expect("valid key")).expect("has letters");
+        assert_eq!(312, candidates.len());
+        assert_eq!(AffineKey::new(7, 19), candidates[0].key);
+        assert_eq!(message, candidates[0].decoded_message);
+        assert!(candidates[1].score < candidates[0].score);
+
+        let stripped = encode("SENDREINFORCEMENTSTOTHENORTHERNBRIDGE", AffineKey::new(3, 2)).expect("valid key");
+        assert_eq!(AffineKey::new(3, 2), crack(&stripped).expect("has letters")[0].key);
+        assert!(matches!(crack("42"), Err(Error::InvalidArgument(_))));
+    }
+
+    #[test]
+    fn test_cipher_trait() {
+        let key = Affine::generate_key(&mut StdRng::seed_from_u64(9), 0);
+        let cipher = Affine::new(key).expect("generated keys are valid");
+        assert_eq!(key, *cipher.key());
+        assert_ne!(1, key.a);
+        assert_eq!("Affine Cipher", cipher.decrypt(&cipher.encrypt("Affine Cipher").expect("encrypt")).expect("decrypt"));
+
+        // huge keys are reduced instead of overflowing
+        let cipher = Affine::new(AffineKey::new(usize::MAX, usize::MAX)).expect("usize::MAX is coprime to 26");
+        assert_eq!(AffineKey::new(15, 15), *cipher.key());
+        let expected = encode("Affine Cipher", AffineKey::new(15, 15)).expect("valid key");
+        assert_eq!(expected, cipher.encrypt("Affine Cipher").expect("encrypt"));
+        assert_eq!(expected, encode("Affine Cipher", AffineKey::new(usize::MAX, usize::MAX)).expect("valid key"));
+        assert_eq!("Affine Cipher", decode(&expected, AffineKey::new(usize::MAX, usize::MAX)).expect("valid key"));
+    }
+}
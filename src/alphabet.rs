@@ -0,0 +1,367 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+
+use once_cell::sync::Lazy;
+
+use crate::error::{Error, Result};
+
+pub static ASCII_LETTERS: Lazy<Alphabet> = Lazy::new(Alphabet::ascii_letters);
+
+const LATIN_DIACRITICS: &str = "àáâãäåæçèéêëìíîïðñòóôõöøœùúûüýþÿß";
+const CYRILLIC: &str = "абвгдеёжзийклмнопрстуфхцчшщъыьэюя";
+const GREEK: &str = "αβγδεζηθικλμνξοπρστυφχψω";
+
+// lower case letters and the ASCII text they transliterate to
+const TRANSLITERATIONS: &[(&str, &str)] = &[
+    ("àáâãäåāăą", "a"), ("æ", "ae"), ("çćĉċč", "c"), ("ďđð", "d"), ("èéêëēĕėęě", "e"),
+    ("ĝğġģ", "g"), ("ĥħ", "h"), ("ìíîïĩīĭįı", "i"), ("ĵ", "j"), ("ķ", "k"), ("ĺļľŀł", "l"),
+    ("ñńņň", "n"), ("òóôõöøōŏő", "o"), ("œ", "oe"), ("ŕŗř", "r"), ("śŝşš", "s"), ("ß", "ss"),
+    ("ţťŧ", "t"), ("þ", "th"), ("ùúûüũūŭůűų", "u"), ("ŵ", "w"), ("ýÿŷ", "y"), ("źżž", "z"),
+];
+
+/// What the ciphers do with letters that are not part of the active alphabet,
+/// such as `é` when working over ASCII letters. Characters that are not
+/// letters (digits, spaces, punctuation, emoji) are always passed through.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ForeignCharPolicy {
+    /// Copy the letter unchanged. Decoding always restores the original text.
+    #[default]
+    PassThrough,
+    /// Fail with [`Error::ForeignCharacter`] pointing at the first foreign letter.
+    Reject,
+    /// Replace the letter with its ASCII transliteration (`é` -> `e`, `ß` -> `ss`)
+    /// before enciphering; letters without one are passed through. Decoding
+    /// restores the transliterated text.
+    Transliterate,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForeignCharError {
+    pub ch: char,
+    /// Index of the offending character, counted in chars.
+    pub position: usize,
+}
+
+impl fmt::Display for ForeignCharError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "letter {:?} at position {} is not part of the alphabet", self.ch, self.position)
+    }
+}
+
+impl std::error::Error for ForeignCharError {}
+
+impl ForeignCharPolicy {
+    /// Applies the policy to `message`, returning the text the cipher should
+    /// run over. Anything still outside `alphabet` is left for the cipher to
+    /// pass through.
+    pub fn prepare<'a>(&self, message: &'a str, alphabet: &Alphabet) -> Result<Cow<'a, str>> {
+        let is_foreign = |ch: char| ch.is_alphabetic() && !alphabet.contains(ch);
+        match self {
+            ForeignCharPolicy::PassThrough => Ok(Cow::Borrowed(message)),
+            ForeignCharPolicy::Reject => match message.chars().enumerate().find(|(_, ch)| is_foreign(*ch)) {
+                Some((position, ch)) => Err(ForeignCharError { ch, position }.into()),
+                None => Ok(Cow::Borrowed(message)),
+            },
+            ForeignCharPolicy::Transliterate => {
+                if !message.chars().any(is_foreign) {
+                    return Ok(Cow::Borrowed(message));
+                }
+                let mut prepared = String::with_capacity(message.len());
+                for (position, ch) in message.chars().enumerate() {
+                    self.prepare_char(ch, position, alphabet, |ch| prepared.push(ch))?;
+                }
+                Ok(Cow::Owned(prepared))
+            }
+        }
+    }
+
+    /// Per character form of [`ForeignCharPolicy::prepare`] for the streaming
+    /// ciphers: calls `emit` with the character(s) to encipher in place of `ch`.
+    pub fn prepare_char(&self, ch: char, position: usize, alphabet: &Alphabet, mut emit: impl FnMut(char)) -> Result<()> {
+        if !ch.is_alphabetic() || alphabet.contains(ch) {
+            emit(ch);
+            return Ok(());
+        }
+        match self {
+            ForeignCharPolicy::PassThrough => emit(ch),
+            ForeignCharPolicy::Reject => return Err(ForeignCharError { ch, position }.into()),
+            ForeignCharPolicy::Transliterate => match transliterate(ch) {
+                Some(ascii) => ascii.chars().for_each(emit),
+                None => emit(ch),
+            },
+        }
+        Ok(())
+    }
+}
+
+/// ASCII transliteration of a Latin letter with diacritics, keeping its case.
+pub fn transliterate(ch: char) -> Option<String> {
+    let mut lower = ch.to_lowercase();
+    let lower = match (lower.next(), lower.next()) {
+        (Some(lower), None) => lower,
+        _ => return None,
+    };
+    let (_, ascii) = TRANSLITERATIONS.iter().find(|(letters, _)| letters.contains(lower))?;
+    if ch.is_uppercase() {
+        Some(ascii.to_ascii_uppercase())
+    } else {
+        Some(ascii.to_string())
+    }
+}
+
+/// An ordered set of symbols that the substitution ciphers shift, reflect and
+/// index over. Letter based alphabets fold case: `'A'` and `'a'` share a
+/// position and ciphers preserve the case of every symbol they rewrite.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Alphabet {
+    symbols: Vec<char>,
+    uppercase: Vec<Option<char>>,
+    index: HashMap<char, (usize, bool)>,
+    // the brute force searches spend most of their time here, so ASCII skips the hash lookup
+    ascii: [Option<(usize, bool)>; 128],
+}
+
+impl Default for Alphabet {
+    fn default() -> Self {
+        Self::ascii_letters()
+    }
+}
+
+impl Alphabet {
+    /// `a-z`, case folded.
+    pub fn ascii_letters() -> Self {
+        Self::build(('a'..='z').collect(), true)
+    }
+
+    /// `a-z`, `A-Z` then `0-9`, case sensitive so that letters shifted onto
+    /// digits (and back) never lose their case.
+    pub fn alphanumeric() -> Self {
+        Self::build(('a'..='z').chain('A'..='Z').chain('0'..='9').collect(), false)
+    }
+
+    /// Every printable ASCII character from space to `~`, case sensitive.
+    pub fn printable_ascii() -> Self {
+        Self::build((' '..='~').collect(), false)
+    }
+
+    /// `a-z` followed by the accented and ligature letters used by western
+    /// European languages, case folded.
+    pub fn latin() -> Self {
+        Self::build(('a'..='z').chain(LATIN_DIACRITICS.chars()).collect(), true)
+    }
+
+    /// The 33 letter Russian alphabet, case folded.
+    pub fn cyrillic() -> Self {
+        Self::build(CYRILLIC.chars().collect(), true)
+    }
+
+    /// The 24 letter Greek alphabet, case folded. The final sigma `ς` is not
+    /// part of the alphabet since it could not be restored on decryption.
+    pub fn greek() -> Self {
+        Self::build(GREEK.chars().collect(), true)
+    }
+
+    /// A case sensitive alphabet made of the characters of `symbols`, in order.
+    pub fn custom(symbols: &str) -> Result<Self> {
+        let symbols: Vec<char> = symbols.chars().collect();
+        if symbols.len() < 2 {
+            return Err(Error::InvalidAlphabet("an alphabet needs at least two symbols".to_string()));
+        }
+        for (i, ch) in symbols.iter().enumerate() {
+            if symbols[..i].contains(ch) {
+                return Err(Error::InvalidAlphabet(format!("symbol {:?} appears more than once", ch)));
+            }
+        }
+        Ok(Self::build(symbols, false))
+    }
+
+    fn build(symbols: Vec<char>, fold_case: bool) -> Self {
+        let mut index: HashMap<char, (usize, bool)> = symbols.iter().
+            enumerate().
+            map(|(i, ch)| (*ch, (i, false))).
+            collect();
+
+        let uppercase = symbols.iter().
+            enumerate().
+            map(|(i, ch)| {
+                if !fold_case {
+                    return None;
+                }
+                // 'ß' upper cases to "SS", use the capital sharp s so the case survives a round trip
+                let mut upper = if *ch == 'ß' { 'ẞ'.to_uppercase() } else { ch.to_uppercase() };
+                match (upper.next(), upper.next()) {
+                    (Some(up), None) if up != *ch && !index.contains_key(&up) => {
+                        index.insert(up, (i, true));
+                        Some(up)
+                    }
+                    _ => None,
+                }
+            }).
+            collect();
+
+        let mut ascii = [None; 128];
+        for (ch, position) in &index {
+            if ch.is_ascii() {
+                ascii[*ch as usize] = Some(*position);
+            }
+        }
+
+        Self { symbols, uppercase, index, ascii }
+    }
+
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    pub fn symbols(&self) -> &[char] {
+        &self.symbols
+    }
+
+    fn lookup(&self, ch: char) -> Option<(usize, bool)> {
+        if ch.is_ascii() {
+            self.ascii[ch as usize]
+        } else {
+            self.index.get(&ch).copied()
+        }
+    }
+
+    pub fn contains(&self, ch: char) -> bool {
+        self.lookup(ch).is_some()
+    }
+
+    /// Position of `ch` in the alphabet, ignoring case for case folded alphabets.
+    pub fn index_of(&self, ch: char) -> Option<usize> {
+        self.lookup(ch).map(|(i, _)| i)
+    }
+
+    /// Positions of the symbols of `text`, skipping everything outside the alphabet.
+    pub fn indices(&self, text: &str) -> Vec<usize> {
+        text.chars().filter_map(|ch| self.index_of(ch)).collect()
+    }
+
+    /// Symbol at `index` (taken modulo the alphabet length), in the case of `like`.
+    pub fn symbol_like(&self, index: usize, like: char) -> char {
+        let index = index % self.len();
+        let upper = self.lookup(like).is_some_and(|(_, upper)| upper);
+        match (upper, self.uppercase[index]) {
+            (true, Some(up)) => up,
+            _ => self.symbols[index],
+        }
+    }
+
+    /// Moves `ch` forward (or back when `forward` is false) by `amount`
+    /// positions, wrapping around. Returns `None` if `ch` is not in the alphabet.
+    pub fn shift(&self, ch: char, amount: usize, forward: bool) -> Option<char> {
+        let i = self.index_of(ch)?;
+        let amount = amount % self.len();
+        let shifted = if forward { i + amount } else { i + self.len() - amount };
+        Some(self.symbol_like(shifted, ch))
+    }
+
+    /// Mirrors `ch` across the alphabet, so the first symbol becomes the last.
+    pub fn reflect(&self, ch: char) -> Option<char> {
+        let i = self.index_of(ch)?;
+        Some(self.symbol_like(self.len() - 1 - i, ch))
+    }
+}
+
+
+// mixes ascii, accented latin, cyrillic, greek, combining marks, cjk and emoji
+// with uniformly random scalar values
+#[cfg(test)]
+pub(crate) fn random_unicode(rng: &mut impl rand::Rng, length: usize) -> String {
+    const POOL: &str = "aZ zé ÉßẞæŒøÅçÇñ ж Ж ё ς Σ ω e\u{301} 東京 🦀 ١٢ 42 ,.!\t\n";
+    let pool: Vec<char> = POOL.chars().collect();
+    (0..length).
+        map(|_| if rng.random_bool(0.5) {
+            pool[rng.random_range(0..pool.len())]
+        } else {
+            rng.random()
+        }).
+        collect()
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_shift() {
+        let letters = Alphabet::ascii_letters();
+        assert_eq!(26, letters.len());
+        assert_eq!(Some('b'), letters.shift('a', 1, true));
+        assert_eq!(Some('A'), letters.shift('Z', 1, true));
+        assert_eq!(Some('Z'), letters.shift('A', 1, false));
+        assert_eq!(None, letters.shift('é', 1, true));
+        assert_eq!(Some('z'), letters.reflect('a'));
+
+        let alphanumeric = Alphabet::alphanumeric();
+        assert_eq!(62, alphanumeric.len());
+        assert_eq!(Some('A'), alphanumeric.shift('z', 1, true));
+        assert_eq!(Some('0'), alphanumeric.shift('Z', 1, true));
+        assert_eq!(Some('a'), alphanumeric.shift('9', 1, true));
+
+        let printable = Alphabet::printable_ascii();
+        assert_eq!(95, printable.len());
+        assert_eq!(Some(' '), printable.shift('~', 1, true));
+        assert_eq!(Some('a'), printable.shift('A', 32, true));
+    }
+
+    #[test]
+    fn test_non_english() {
+        let cyrillic = Alphabet::cyrillic();
+        assert_eq!(33, cyrillic.len());
+        assert_eq!(Some('Б'), cyrillic.shift('А', 1, true));
+        assert_eq!(Some('а'), cyrillic.shift('я', 1, true));
+
+        let greek = Alphabet::greek();
+        assert_eq!(24, greek.len());
+        assert_eq!(Some('Ω'), greek.reflect('Α'));
+        assert!(!greek.contains('ς'));
+
+        let latin = Alphabet::latin();
+        assert_eq!('É', latin.symbol_like(latin.index_of('é').expect("in alphabet"), 'A'));
+        assert_eq!(Some('ß'), latin.shift('þ', 2, true));
+        assert_eq!(Some('ẞ'), latin.shift('Þ', 2, true));
+    }
+
+    #[test]
+    fn test_policy() {
+        let letters = Alphabet::ascii_letters();
+        let message = "Straße café, 東京 42!";
+
+        assert_eq!(message, ForeignCharPolicy::PassThrough.prepare(message, &letters).expect("pass through"));
+        assert!(matches!(
+            ForeignCharPolicy::Reject.prepare(message, &letters),
+            Err(Error::ForeignCharacter(ForeignCharError { ch: 'ß', position: 4 }))
+        ));
+        assert_eq!("Hello, 42!", ForeignCharPolicy::Reject.prepare("Hello, 42!", &letters).expect("only ascii letters"));
+        assert_eq!("Strasse cafe, 東京 42!", ForeignCharPolicy::Transliterate.prepare(message, &letters).expect("transliterate"));
+
+        // letters that are part of the alphabet are never transliterated
+        let latin = Alphabet::latin();
+        assert_eq!(message, ForeignCharPolicy::Transliterate.prepare(message, &latin).expect("transliterate"));
+
+        assert_eq!(Some("AE".to_string()), transliterate('Æ'));
+        assert_eq!(Some("th".to_string()), transliterate('þ'));
+        assert_eq!(None, transliterate('ж'));
+    }
+
+    #[test]
+    fn test_custom() {
+        let custom = Alphabet::custom("01").expect("valid alphabet");
+        assert_eq!(Some('0'), custom.shift('1', 1, true));
+        assert_eq!(None, custom.shift('2', 1, true));
+        assert!(Alphabet::custom("a").is_err());
+        assert!(Alphabet::custom("abca").is_err());
+
+        let case_sensitive = Alphabet::custom("abAB").expect("valid alphabet");
+        assert_eq!(Some('A'), case_sensitive.shift('a', 2, true));
+    }
+}
@@ -1,56 +1,98 @@
-use std::collections::HashMap;
-use std::sync::Arc;
+use std::io;
 
-use once_cell::sync::Lazy;
+use rand::{CryptoRng, RngCore};
 
-type English = Arc<HashMap<char, char>>;
+use crate::alphabet::{Alphabet, ASCII_LETTERS};
+use crate::cipher::Cipher;
+use crate::error::Result;
+use crate::keygen::GenerateKey;
+use crate::stream::{CharTransform, StreamingCipher, TextTransform};
 
-trait SimpleHash<K, V> {
-    fn get<Q>(&self, k: &Q) -> Option<&V>
-        where
-            K: std::borrow::Borrow<Q>,
-            Q: std::hash::Hash + Eq + ?Sized;
+pub fn encode(message: &str) -> String {
+    handle(message, &ASCII_LETTERS)
 }
 
-static ENGLISH_ALPHA_HASH: Lazy<English> = Lazy::new(|| {
-    let alpha = ('a'..='z').
-        filter(|ch| ch.is_ascii_alphabetic());
-    let rev = alpha.clone().rev();
-    Arc::new(HashMap::from_iter(
-        alpha.zip(rev)
-    ))
-});
-
-static ENGLISH_ALPHA_HASH_REV: Lazy<English> = Lazy::new(|| {
-    let alpha = ('a'..='z').
-        filter(|ch| ch.is_ascii_alphabetic());
-    let rev = alpha.clone().rev();
-    Arc::new(HashMap::from_iter(
-        rev.zip(alpha)
-    ))
-});
+pub fn decode(message: &str) -> String {
+    handle(message, &ASCII_LETTERS)
+}
 
-pub fn encode(message: &str) -> String {
-    handle(message, &ENGLISH_ALPHA_HASH)
+pub fn encode_with(message: &str, alphabet: &Alphabet) -> String {
+    handle(message, alphabet)
 }
 
-pub fn decode(message: &str) -> String {
-    handle(message, &ENGLISH_ALPHA_HASH_REV)
+pub fn decode_with(message: &str, alphabet: &Alphabet) -> String {
+    handle(message, alphabet)
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Atbash {
+    key: (),
+    alphabet: Alphabet,
+}
+
+impl Atbash {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_alphabet(alphabet: Alphabet) -> Self {
+        Self { key: (), alphabet }
+    }
+}
+
+impl Cipher for Atbash {
+    type Key = ();
+
+    fn validate_key(_key: &()) -> Result<()> {
+        Ok(())
+    }
+
+    fn key(&self) -> &() {
+        &self.key
+    }
+
+    fn encrypt(&self, message: &str) -> Result<String> {
+        Ok(encode_with(message, &self.alphabet))
+    }
+
+    fn decrypt(&self, message: &str) -> Result<String> {
+        Ok(decode_with(message, &self.alphabet))
+    }
 }
 
-fn handle(message: &str, hash: &Lazy<English>) -> String {
+impl StreamingCipher for Atbash {
+    type Encryptor = TextTransform<AtbashStream>;
+    type Decryptor = TextTransform<AtbashStream>;
+
+    fn encryptor(&self) -> Self::Encryptor {
+        TextTransform::new(AtbashStream { alphabet: self.alphabet.clone() })
+    }
+
+    fn decryptor(&self) -> Self::Decryptor {
+        self.encryptor()
+    }
+}
+
+pub struct AtbashStream {
+    alphabet: Alphabet,
+}
+
+impl CharTransform for AtbashStream {
+    fn transform(&mut self, ch: char, output: &mut String) -> io::Result<()> {
+        output.push(self.alphabet.reflect(ch).unwrap_or(ch));
+        Ok(())
+    }
+}
+
+impl GenerateKey for Atbash {
+    fn generate_key<R: RngCore + CryptoRng + ?Sized>(_rng: &mut R, _length: usize) {}
+}
+
+// atbash is its own inverse, the same reflection both encodes and decodes
+fn handle(message: &str, alphabet: &Alphabet) -> String {
     message.chars().
-        map(|ch| {
-            if ch.is_alphabetic() {
-                if ch.is_ascii_uppercase() {
-                    let translation = hash.get(&ch.to_ascii_lowercase()).expect("all english letters should be present");
-                    return translation.to_ascii_uppercase();
-                }
-                *hash.get(&ch).expect("all english letters should be present")
-            } else {
-                ch
-            }
-        }).collect()
+        map(|ch| alphabet.reflect(ch).unwrap_or(ch)).
+        collect()
 }
 
 
@@ -61,11 +103,28 @@ mod test {
     #[test]
     fn test_encode() {
         assert_eq!("Hzoob hvooh hvz hsvooh yb gsv hvzhsliv.", encode("Sally sells sea shells by the seashore."));
-        handle("hello", &ENGLISH_ALPHA_HASH);
+        handle("hello", &ASCII_LETTERS);
     }
 
     #[test]
     fn test_decode() {
         assert_eq!("Sally sells sea shells by the seashore.", decode("Hzoob hvooh hvz hsvooh yb gsv hvzhsliv."))
     }
+
+    #[test]
+    fn test_alphabets() {
+        let cyrillic = Alphabet::cyrillic();
+        assert_eq!("Яюэ", encode_with("Абв", &cyrillic));
+        assert_eq!("Абв", decode_with("Яюэ", &cyrillic));
+
+        let cipher = Atbash::with_alphabet(Alphabet::printable_ascii());
+        assert_eq!("~}|", cipher.encrypt(" !\"").expect("encrypt"));
+    }
+
+    #[test]
+    fn test_cipher_trait() {
+        let cipher = Atbash::new();
+        assert_eq!("Hzoob hvooh", cipher.encrypt("Sally sells").expect("encrypt"));
+        assert_eq!("Sally sells", cipher.decrypt("Hzoob hvooh").expect("decrypt"));
+    }
 }
@@ -0,0 +1,281 @@
+use std::collections::VecDeque;
+use std::io;
+
+use rand::{CryptoRng, RngCore};
+
+use crate::alphabet::{Alphabet, ASCII_LETTERS};
+use crate::candidate::{self, Candidate};
+use crate::cipher::Cipher;
+use crate::detection::detect_english::Detector;
+use crate::detection::ngram::NgramScorer;
+use crate::error::{Error, Result};
+use crate::keygen::GenerateKey;
+use crate::stream::{CharTransform, StreamingCipher, TextTransform};
+
+/// Primers up to this many letters are all tried by [`crack`].
+const SHORT_PRIMER: usize = 3;
+
+/// Which text extends the primer once it runs out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Feedback {
+    /// The key continues with the plaintext, the classic autokey.
+    #[default]
+    Plaintext,
+    /// The key continues with the ciphertext.
+    Ciphertext,
+}
+
+pub fn encode(message: &str, primer: &str, feedback: Feedback) -> Result<String> {
+    encode_with(message, primer, feedback, &ASCII_LETTERS)
+}
+
+
+pub fn decode(message: &str, primer: &str, feedback: Feedback) -> Result<String> {
+    decode_with(message, primer, feedback, &ASCII_LETTERS)
+}
+
+
+pub fn encode_with(message: &str, primer: &str, feedback: Feedback, alphabet: &Alphabet) -> Result<String> {
+    Ok(AutokeyStream::new(primer, alphabet.clone(), feedback, true)?.apply(message))
+}
+
+
+pub fn decode_with(message: &str, primer: &str, feedback: Feedback, alphabet: &Alphabet) -> Result<String> {
+    Ok(AutokeyStream::new(primer, alphabet.clone(), feedback, false)?.apply(message))
+}
+
+
+/// Searches primers for an English message: every primer of up to three
+/// letters, then the first `limit` dictionary words. The decryptions are
+/// ranked by the share of their letters that form dictionary words, best
+/// first. With ciphertext feedback the primer only decides the first few
+/// letters, so ties are common; they are broken by n-gram fitness.
+pub fn crack(message: &str, feedback: Feedback, limit: usize) -> Result<Vec<Candidate<String>>> {
+    let detection = Detector::new_with_fix_db()?;
+    crack_with(message, feedback, limit, &detection)
+}
+
+
+/// Like [`crack`] with an already loaded detector.
+pub fn crack_with(message: &str, feedback: Feedback, limit: usize, detection: &Detector) -> Result<Vec<Candidate<String>>> {
+    if ASCII_LETTERS.indices(message).is_empty() {
+        return Err(Error::InvalidArgument("message has no letters to crack".to_string()));
+    }
+    let mut short = vec![String::new()];
+    let mut primers = Vec::new();
+    for _ in 0..SHORT_PRIMER {
+        short = short.iter().
+            flat_map(|primer| ASCII_LETTERS.symbols()[..26].iter().map(move |&ch| format!("{}{}", primer, ch))).
+            collect();
+        primers.extend(short.iter().cloned());
+    }
+    primers.extend(detection.iter_dictionary_words().
+        filter(|word| word.len() > SHORT_PRIMER && word.chars().all(|ch| ch.is_ascii_lowercase())).
+        take(limit));
+
+    let scorer = NgramScorer::english_quadgrams()?;
+    let mut scored: Vec<(Candidate<String>, f64)> = primers.into_iter().
+        map(|primer| {
+            let msg = decode(message, &primer, feedback).expect("primers are made of letters");
+            let score = detection.score(&msg);
+            let fitness = scorer.score(&msg);
+            (Candidate::new(primer, msg, score), fitness)
+        }).
+        collect();
+    // ranking is stable, so sorting by fitness first breaks the ties
+    scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+    let mut candidates: Vec<Candidate<String>> = scored.into_iter().map(|(candidate, _)| candidate).collect();
+    candidate::rank(&mut candidates);
+    Ok(candidates)
+}
+
+/// Autokey Vigenere cipher: the primer starts the key, which then continues
+/// with the message itself (or the ciphertext, see [`Feedback`]). Characters
+/// outside the alphabet pass through and do not use up key letters.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Autokey {
+    primer: String,
+    alphabet: Alphabet,
+    feedback: Feedback,
+}
+
+impl Autokey {
+    pub fn new(primer: &str) -> Result<Self> {
+        Self::with_alphabet(primer, ASCII_LETTERS.clone())
+    }
+
+    pub fn with_alphabet(primer: &str, alphabet: Alphabet) -> Result<Self> {
+        if alphabet.indices(primer).is_empty() {
+            return Err(Error::InvalidKey("autokey primer must contain at least one symbol of the alphabet".to_string()));
+        }
+        Ok(Self { primer: primer.to_string(), alphabet, feedback: Feedback::default() })
+    }
+
+    pub fn with_feedback(mut self, feedback: Feedback) -> Self {
+        self.feedback = feedback;
+        self
+    }
+
+    fn stream(&self, encode: bool) -> AutokeyStream {
+        AutokeyStream::new(&self.primer, self.alphabet.clone(), self.feedback, encode).expect("primer validated on construction")
+    }
+}
+
+impl Cipher for Autokey {
+    type Key = String;
+
+    fn validate_key(key: &String) -> Result<()> {
+        if !key.chars().any(|ch| ch.is_ascii_alphabetic()) {
+            return Err(Error::InvalidKey("autokey primer must contain at least one ascii letter".to_string()));
+        }
+        Ok(())
+    }
+
+    fn key(&self) -> &String {
+        &self.primer
+    }
+
+    fn encrypt(&self, message: &str) -> Result<String> {
+        Ok(self.stream(true).apply(message))
+    }
+
+    fn decrypt(&self, message: &str) -> Result<String> {
+        Ok(self.stream(false).apply(message))
+    }
+}
+
+impl StreamingCipher for Autokey {
+    type Encryptor = TextTransform<AutokeyStream>;
+    type Decryptor = TextTransform<AutokeyStream>;
+
+    fn encryptor(&self) -> Self::Encryptor {
+        TextTransform::new(self.stream(true))
+    }
+
+    fn decryptor(&self) -> Self::Decryptor {
+        TextTransform::new(self.stream(false))
+    }
+}
+
+/// Character by character form of the autokey cipher. Holds the key letters
+/// not used yet: the rest of the primer followed by the fed back text.
+pub struct AutokeyStream {
+    key: VecDeque<usize>,
+    alphabet: Alphabet,
+    feedback: Feedback,
+    encode: bool,
+}
+
+impl AutokeyStream {
+    pub fn new(primer: &str, alphabet: Alphabet, feedback: Feedback, encode: bool) -> Result<Self> {
+        let key: VecDeque<usize> = alphabet.indices(primer).into();
+        if key.is_empty() {
+            return Err(Error::InvalidKey("autokey primer must contain at least one symbol of the alphabet".to_string()));
+        }
+        Ok(Self { key, alphabet, feedback, encode })
+    }
+
+    fn step(&mut self, ch: char) -> char {
+        let Some(x) = self.alphabet.index_of(ch) else {
+            return ch;
+        };
+        let k = self.key.pop_front().expect("one key letter queued per letter seen");
+        let size = self.alphabet.len();
+        let y = if self.encode { (x + k) % size } else { (x + size - k) % size };
+        let (plain, cipher) = if self.encode { (x, y) } else { (y, x) };
+        self.key.push_back(match self.feedback {
+            Feedback::Plaintext => plain,
+            Feedback::Ciphertext => cipher,
+        });
+        self.alphabet.symbol_like(y, ch)
+    }
+
+    fn apply(&mut self, message: &str) -> String {
+        message.chars().map(|ch| self.step(ch)).collect()
+    }
+}
+
+impl CharTransform for AutokeyStream {
+    fn transform(&mut self, ch: char, output: &mut String) -> io::Result<()> {
+        output.push(self.step(ch));
+        Ok(())
+    }
+}
+
+impl GenerateKey for Autokey {
+    fn generate_key<R: RngCore + CryptoRng + ?Sized>(rng: &mut R, length: usize) -> String {
+        crate::keygen::random_symbols(rng, &ASCII_LETTERS, length.max(1))
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+
+    const MESSAGE: &str = "Be normal, and the crowd will accept you. Be deranged, and they will make you their leader.";
+
+    #[test]
+    fn test_encode() {
+        let encoded = encode("attack at dawn", "queenly", Feedback::Plaintext).expect("valid primer");
+        assert_eq!("qnxepv yt wtwp", encoded);
+        assert_eq!("attack at dawn", decode(&encoded, "queenly", Feedback::Plaintext).expect("valid primer"));
+
+        let encoded = encode("Attack at Dawn!", "queen", Feedback::Ciphertext).expect("valid primer");
+        assert_eq!("Qnxepa nq Hpwa!", encoded);
+        assert_eq!("Attack at Dawn!", decode(&encoded, "queen", Feedback::Ciphertext).expect("valid primer"));
+
+        assert!(matches!(encode("text", "42", Feedback::Plaintext), Err(Error::InvalidKey(_))));
+    }
+
+    #[test]
+    fn test_alphabets() {
+        let cyrillic = Alphabet::cyrillic();
+        for feedback in [Feedback::Plaintext, Feedback::Ciphertext] {
+            let encoded = encode_with("Съешь же ещё этих булок", "ключ", feedback, &cyrillic).expect("valid primer");
+            assert_eq!("Съешь же ещё этих булок", decode_with(&encoded, "ключ", feedback, &cyrillic).expect("valid primer"));
+        }
+    }
+
+    #[test]
+    fn test_cipher_trait() {
+        assert!(Autokey::new("").is_err());
+        let mut rng = StdRng::seed_from_u64(22);
+        for feedback in [Feedback::Plaintext, Feedback::Ciphertext] {
+            let cipher = Autokey::new(&Autokey::generate_key(&mut rng, 6)).expect("generated keys are valid").with_feedback(feedback);
+            assert_eq!(6, cipher.key().len());
+            assert_eq!(MESSAGE, cipher.decrypt(&cipher.encrypt(MESSAGE).expect("encrypt")).expect("decrypt"));
+        }
+    }
+
+    #[test]
+    fn test_crack() {
+        let detection = Detector::new_with_fix_db().expect("dictionary");
+        let encoded = encode(MESSAGE, "owl", Feedback::Plaintext).expect("valid primer");
+        let candidates = crack_with(&encoded, Feedback::Plaintext, 0, &detection).expect("has letters");
+        assert_eq!(MESSAGE, candidates[0].decoded_message);
+        assert_eq!("owl", candidates[0].key);
+
+        // with ciphertext feedback the primer only shows in its own letters: "Be formal" reads as well
+        let encoded = encode(MESSAGE, "fox", Feedback::Ciphertext).expect("valid primer");
+        let candidates = crack_with(&encoded, Feedback::Ciphertext, 0, &detection).expect("has letters");
+        assert_eq!(MESSAGE[4..], candidates[0].decoded_message[4..]);
+        let fox = candidates.iter().find(|candidate| candidate.key == "fox").expect("short primer");
+        assert_eq!(candidates[0].score, fox.score);
+        assert!(matches!(crack_with("1984", Feedback::Plaintext, 10, &detection), Err(Error::InvalidArgument(_))));
+    }
+
+    #[test]
+    fn test_crack_dictionary() {
+        // the dictionary is tried in alphabetical order, "abbot" is within its first 300 words longer than three letters
+        let detection = Detector::new_with_fix_db().expect("dictionary");
+        let encoded = encode(MESSAGE, "abbot", Feedback::Plaintext).expect("valid primer");
+        let candidates = crack_with(&encoded, Feedback::Plaintext, 300, &detection).expect("has letters");
+        assert_eq!(MESSAGE, candidates[0].decoded_message);
+        assert_eq!("abbot", candidates[0].key);
+        assert_eq!(26 + 26 * 26 + 26 * 26 * 26 + 300, candidates.len());
+    }
+}
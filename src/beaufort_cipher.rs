@@ -0,0 +1,106 @@
+//! Beaufort (`c = k - p`) and variant Beaufort (`c = p - k`) ciphers, run on
+//! the keyed engine of [`crate::vigenere_cipher`]. Build a [`Vigenere`] with
+//! [`Vigenere::with_mode`] for the `Cipher` and streaming forms.
+//!
+//! [`Vigenere`]: crate::vigenere_cipher::Vigenere
+//! [`Vigenere::with_mode`]: crate::vigenere_cipher::Vigenere::with_mode
+
+use crate::alphabet::{Alphabet, ASCII_LETTERS};
+use crate::error::Result;
+use crate::vigenere_cipher::{self, BruteForceResponse, Mode};
+
+/// Beaufort is its own inverse, `decode` is the same as `encode`.
+pub fn encode(message: &str, key: &str) -> String {
+    vigenere_cipher::encode_with_mode(message, key, &ASCII_LETTERS, Mode::Beaufort)
+}
+
+
+pub fn decode(message: &str, key: &str) -> String {
+    vigenere_cipher::decode_with_mode(message, key, &ASCII_LETTERS, Mode::Beaufort)
+}
+
+
+pub fn encode_with(message: &str, key: &str, alphabet: &Alphabet) -> String {
+    vigenere_cipher::encode_with_mode(message, key, alphabet, Mode::Beaufort)
+}
+
+
+pub fn decode_with(message: &str, key: &str, alphabet: &Alphabet) -> String {
+    vigenere_cipher::decode_with_mode(message, key, alphabet, Mode::Beaufort)
+}
+
+
+/// Variant Beaufort encodes with the Vigenere decryption.
+pub fn encode_variant(message: &str, key: &str) -> String {
+    vigenere_cipher::encode_with_mode(message, key, &ASCII_LETTERS, Mode::VariantBeaufort)
+}
+
+
+pub fn decode_variant(message: &str, key: &str) -> String {
+    vigenere_cipher::decode_with_mode(message, key, &ASCII_LETTERS, Mode::VariantBeaufort)
+}
+
+
+pub fn decode_brute_force(message: &str, limit: usize) -> Result<BruteForceResponse> {
+    vigenere_cipher::brute_force(message, limit, Mode::Beaufort)
+}
+
+
+pub fn decode_variant_brute_force(message: &str, limit: usize) -> Result<BruteForceResponse> {
+    vigenere_cipher::brute_force(message, limit, Mode::VariantBeaufort)
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cipher::Cipher;
+    use crate::vigenere_cipher::Vigenere;
+
+    #[test]
+    fn test_encode() {
+        assert_eq!("CKMPVCPVWPIWUJOGIUAPVWRIWUUK", encode("DEFENDTHEEASTWALLOFTHECASTLE", "FORTIFICATION"));
+        assert_eq!("DEFENDTHEEASTWALLOFTHECASTLE", decode("CKMPVCPVWPIWUJOGIUAPVWRIWUUK", "FORTIFICATION"));
+        // reciprocal
+        assert_eq!("Attack at dawn!", encode(&encode("Attack at dawn!", "lemon"), "lemon"));
+    }
+
+    #[test]
+    fn test_encode_variant() {
+        let message = "Be normal, and the crowd will accept you";
+        let encoded = encode_variant(message, "randalthor");
+        assert_eq!(vigenere_cipher::decode(message, "randalthor"), encoded);
+        assert_eq!(message, decode_variant(&encoded, "randalthor"));
+        assert_ne!(encode(message, "randalthor"), encoded);
+    }
+
+    #[test]
+    fn test_alphabets() {
+        let greek = Alphabet::greek();
+        let encoded = encode_with("Καλημέρα", "κλειδί", &greek);
+        assert_eq!("Καλημέρα", decode_with(&encoded, "κλειδί", &greek));
+    }
+
+    #[test]
+    fn test_cipher_trait() {
+        let cipher = Vigenere::new("FORTIFICATION").expect("valid key").with_mode(Mode::Beaufort);
+        assert_eq!("CKMPVCPVWPIWUJOGIUAPVWRIWUUK", cipher.encrypt("DEFENDTHEEASTWALLOFTHECASTLE").expect("encrypt"));
+        assert_eq!("DEFENDTHEEASTWALLOFTHECASTLE", cipher.decrypt("CKMPVCPVWPIWUJOGIUAPVWRIWUUK").expect("decrypt"));
+
+        let cipher = cipher.with_mode(Mode::VariantBeaufort);
+        assert_eq!("Lemon", cipher.decrypt(&cipher.encrypt("Lemon").expect("encrypt")).expect("decrypt"));
+    }
+
+    #[test]
+    fn test_decode_brute_force() {
+        // the dictionary is tried in alphabetical order, "abbot" is one of its first thousand words
+        let message = "Be normal, and the crowd will accept you. Be deranged, and they will make you their leader";
+        let response = decode_brute_force(&encode(message, "abbot"), 1000).expect("dictionary key");
+        assert_eq!(message, response.decoded_message);
+        assert_eq!("abbot", response.key);
+
+        let response = decode_variant_brute_force(&encode_variant(message, "abbot"), 1000).expect("dictionary key");
+        assert_eq!(message, response.decoded_message);
+        assert!(decode_brute_force(&encode(message, "love"), 1000).is_err(), "love is far past the limit");
+    }
+}
@@ -0,0 +1,27 @@
+use clap::Parser;
+use clap_derive::Parser;
+
+use rusty_cryptography_kit::detection::ngram::NgramCounts;
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct Args {
+    #[arg(short, required = true)]
+    corpus_path: String,
+    #[arg(short, default_value_t = 4)]
+    n: usize,
+    #[arg(short, default_value_t = 1)]
+    min_count: u64,
+    #[arg(short, default_value = "src/detection/ngram_db/english_quadgrams.json.gz")]
+    save_ngram_data_path: String,
+}
+
+
+fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+    let text = std::fs::read_to_string(args.corpus_path)?;
+    let mut counts = NgramCounts::from_text(args.n, &text)?;
+    counts.prune(args.min_count);
+    counts.to_json_file(&args.save_ngram_data_path)?;
+    Ok(())
+}
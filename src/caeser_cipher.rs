@@ -1,49 +1,249 @@
+use std::io;
+
+use rand::{CryptoRng, Rng, RngCore};
+
+use crate::alphabet::{Alphabet, ForeignCharPolicy, ASCII_LETTERS};
+use crate::candidate::{self, Candidate};
+use crate::cipher::Cipher;
+use crate::detection::detect_english::Detector;
+use crate::detection::frequency::LanguageProfile;
+use crate::error::{Error, Result};
+use crate::keygen::GenerateKey;
+use crate::stream::{CharTransform, StreamingCipher, TextTransform};
+
 pub fn encode(message: &str, key: u8) -> String {
-    let key = key % 26;
-    handle(message, key, true)
+    handle(message, key as usize, &ASCII_LETTERS, true)
 }
 
 
 pub fn decode(message: &str, key: u8) -> String {
-    let key = key % 26;
-    handle(message, key, false)
+    handle(message, key as usize, &ASCII_LETTERS, false)
+}
+
+
+pub fn encode_with(message: &str, key: usize, alphabet: &Alphabet) -> String {
+    handle(message, key, alphabet, true)
+}
+
+
+pub fn decode_with(message: &str, key: usize, alphabet: &Alphabet) -> String {
+    handle(message, key, alphabet, false)
+}
+
+
+/// Like [`encode_with`], applying `policy` to letters that are not part of `alphabet`.
+pub fn encode_with_policy(message: &str, key: usize, alphabet: &Alphabet, policy: ForeignCharPolicy) -> Result<String> {
+    let message = policy.prepare(message, alphabet)?;
+    Ok(handle(&message, key, alphabet, true))
+}
+
+
+/// Like [`decode_with`], applying `policy` to letters that are not part of `alphabet`.
+pub fn decode_with_policy(message: &str, key: usize, alphabet: &Alphabet, policy: ForeignCharPolicy) -> Result<String> {
+    let message = policy.prepare(message, alphabet)?;
+    Ok(handle(&message, key, alphabet, false))
 }
 
 
 #[derive(Debug, PartialEq)]
 pub struct BruteForceResponse {
-    decoded_message: String,
-    key: u8,
+    pub decoded_message: String,
+    pub key: u8,
 }
 
-pub fn decode_brute_force(message: &str) -> anyhow::Result<BruteForceResponse> {
-    let detection = crate::detection::detect_english::Detector::new_with_fix_db()?;
+pub fn decode_brute_force(message: &str) -> Result<BruteForceResponse> {
+    let detection = Detector::new_with_fix_db()?;
     for i in 1..=26u8 {
         let msg = decode(message, i);
         if detection.is_english(&msg) {
             return Ok(BruteForceResponse { decoded_message: msg, key: i });
         }
     }
-    anyhow::bail!("message could not be decoded")
+    Err(Error::ExhaustedSearch)
 }
 
-fn handle(message: &str, key: u8, encode: bool) -> String {
-    message.chars().
-        map(|ch|
-            if ch.is_alphabetic() {
-                let base = if ch.is_ascii_uppercase() { 'A' as u8 } else { 'a' as u8 };
-                let ch = ch as u8;
-                let key = if encode { key } else { 26 - key };
-                (base + (ch - base + key) % 26) as char
-            } else {
-                ch
+/// Every shift from 1 to 25 with its [`Detector::score`], best first, for when
+/// the message is too short or ambiguous to trust a single answer.
+pub fn rank_brute_force(message: &str) -> Result<Vec<Candidate<u8>>> {
+    let detection = Detector::new_with_fix_db()?;
+    Ok(rank_brute_force_with(message, &detection))
+}
+
+
+/// Like [`rank_brute_force`] with an already loaded detector.
+pub fn rank_brute_force_with(message: &str, detection: &Detector) -> Vec<Candidate<u8>> {
+    let mut candidates: Vec<Candidate<u8>> = (1..26u8).
+        map(|key| {
+            let msg = decode(message, key);
+            let score = detection.score(&msg);
+            Candidate::new(key, msg, score)
+        }).
+        collect();
+    candidate::rank(&mut candidates);
+    candidates
+}
+
+/// Result of the statistical solver.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrequencyResponse {
+    pub decoded_message: String,
+    pub key: usize,
+    /// Chi-squared distance of the decoded letters from the language profile.
+    pub chi_squared: f64,
+    /// How far ahead of the runner-up the best shift is, from 0 (a tie, the
+    /// answer is a guess) to 1. Short messages tend to have small margins.
+    pub margin: f64,
+}
+
+/// Recovers the shift of an English message from its letter frequencies
+/// alone, see [`decode_chi_squared_with`].
+pub fn decode_chi_squared(message: &str) -> Result<FrequencyResponse> {
+    decode_chi_squared_with(message, &LanguageProfile::english())
+}
+
+
+/// Recovers the shift by picking the one whose decoded letters are closest to
+/// `profile`. Needs no dictionary, so it copes with proper nouns and text
+/// whose spaces have been stripped.
+pub fn decode_chi_squared_with(message: &str, profile: &LanguageProfile) -> Result<FrequencyResponse> {
+    let candidates = rank_chi_squared(message, profile);
+    let (best, runner_up) = (&candidates[0], &candidates[1]);
+    if best.score.is_infinite() {
+        return Err(Error::InvalidArgument("message has no letters of the alphabet to analyse".to_string()));
+    }
+    let (chi_squared, second) = (-best.score, -runner_up.score);
+    Ok(FrequencyResponse {
+        decoded_message: best.decoded_message.clone(),
+        key: best.key,
+        chi_squared,
+        margin: (second - chi_squared) / second,
+    })
+}
+
+
+/// Every shift of `profile`'s alphabet, best first. The score is the negated
+/// chi-squared distance so that higher is better.
+pub fn rank_chi_squared(message: &str, profile: &LanguageProfile) -> Vec<Candidate<usize>> {
+    let alphabet = profile.alphabet();
+    let counts = profile.count(message);
+    let mut shifted = vec![0; counts.len()];
+    let mut candidates: Vec<Candidate<usize>> = (0..alphabet.len()).
+        map(|key| {
+            // letter i of the plaintext is letter i + key of the ciphertext
+            for (i, count) in shifted.iter_mut().enumerate() {
+                *count = counts[(i + key) % counts.len()];
             }
-        ).collect()
+            let score = -profile.chi_squared_counts(&shifted);
+            Candidate::new(key, decode_with(message, key, alphabet), score)
+        }).
+        collect();
+    candidate::rank(&mut candidates);
+    candidates
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Caesar {
+    key: u8,
+    alphabet: Alphabet,
+    policy: ForeignCharPolicy,
+}
+
+impl Caesar {
+    pub fn new(key: u8) -> Result<Self> {
+        Self::with_alphabet(key, ASCII_LETTERS.clone())
+    }
+
+    pub fn with_alphabet(key: u8, alphabet: Alphabet) -> Result<Self> {
+        Self::validate_key(&key)?;
+        Ok(Self { key, alphabet, policy: ForeignCharPolicy::default() })
+    }
+
+    pub fn with_policy(mut self, policy: ForeignCharPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+}
+
+impl Cipher for Caesar {
+    type Key = u8;
+
+    fn validate_key(_key: &u8) -> Result<()> {
+        // every shift is reduced modulo the alphabet length, so any byte is a usable key
+        Ok(())
+    }
+
+    fn key(&self) -> &u8 {
+        &self.key
+    }
+
+    fn encrypt(&self, message: &str) -> Result<String> {
+        encode_with_policy(message, self.key as usize, &self.alphabet, self.policy)
+    }
+
+    fn decrypt(&self, message: &str) -> Result<String> {
+        decode_with_policy(message, self.key as usize, &self.alphabet, self.policy)
+    }
+}
+
+impl StreamingCipher for Caesar {
+    type Encryptor = TextTransform<CaesarStream>;
+    type Decryptor = TextTransform<CaesarStream>;
+
+    fn encryptor(&self) -> Self::Encryptor {
+        TextTransform::new(CaesarStream::new(self.key as usize, self.alphabet.clone(), self.policy, true))
+    }
+
+    fn decryptor(&self) -> Self::Decryptor {
+        TextTransform::new(CaesarStream::new(self.key as usize, self.alphabet.clone(), self.policy, false))
+    }
+}
+
+/// Character by character form of the Caesar shift, used by the streaming adapters.
+pub struct CaesarStream {
+    key: usize,
+    alphabet: Alphabet,
+    policy: ForeignCharPolicy,
+    encode: bool,
+    position: usize,
+}
+
+impl CaesarStream {
+    pub fn new(key: usize, alphabet: Alphabet, policy: ForeignCharPolicy, encode: bool) -> Self {
+        Self { key, alphabet, policy, encode, position: 0 }
+    }
+}
+
+impl CharTransform for CaesarStream {
+    fn transform(&mut self, ch: char, output: &mut String) -> io::Result<()> {
+        let alphabet = &self.alphabet;
+        let (key, encode) = (self.key, self.encode);
+        self.policy.prepare_char(ch, self.position, alphabet, |ch| output.push(alphabet.shift(ch, key, encode).unwrap_or(ch))).
+            map_err(io::Error::from)?;
+        self.position += 1;
+        Ok(())
+    }
+}
+
+impl GenerateKey for Caesar {
+    // a shift for the ASCII letters of `Caesar::new`; 0 would leave the message unchanged
+    fn generate_key<R: RngCore + CryptoRng + ?Sized>(rng: &mut R, _length: usize) -> u8 {
+        rng.random_range(1..ASCII_LETTERS.len() as u8)
+    }
+}
+
+fn handle(message: &str, key: usize, alphabet: &Alphabet, encode: bool) -> String {
+    message.chars().
+        map(|ch| alphabet.shift(ch, key, encode).unwrap_or(ch)).
+        collect()
 }
 
 #[cfg(test)]
 mod test {
+    use rand::{Rng, SeedableRng};
+    use rand::rngs::StdRng;
+
     use super::*;
+    use crate::alphabet::ForeignCharError;
 
     #[test]
     fn test_encode() {
@@ -76,6 +276,113 @@ mod test {
             got_decode
         );
     }
+
+    #[test]
+    fn test_rank_brute_force() {
+        let candidates = rank_brute_force("Wkh vhfuhw lv lq wkh jdughq").expect("dictionary available");
+        assert_eq!(25, candidates.len());
+        assert_eq!(3, candidates[0].key);
+        assert_eq!("The secret is in the garden", candidates[0].decoded_message);
+        assert_eq!(1.0, candidates[0].score);
+        assert!(candidates[1].score < candidates[0].score);
+        assert!(candidates.windows(2).all(|pair| pair[0].score >= pair[1].score));
+
+        let mut keys: Vec<u8> = candidates.iter().map(|candidate| candidate.key).collect();
+        keys.sort();
+        assert_eq!((1..26).collect::<Vec<u8>>(), keys);
+    }
+
+    #[test]
+    fn test_decode_chi_squared() {
+        let message = "Defend the east wall of the castle until reinforcements from Richmond arrive";
+        let encoded = encode(message, 11);
+        let response = decode_chi_squared(&encoded).expect("has letters");
+        assert_eq!(11, response.key);
+        assert_eq!(message, response.decoded_message);
+        assert!(response.margin > 0.5, "margin {}", response.margin);
+
+        // no spaces, so no dictionary word would match
+        let stripped = encode("MeetMrsHolloway at Paddington station behind the newspaper stand", 20).replace(' ', "");
+        assert_eq!(20, decode_chi_squared(&stripped).expect("has letters").key);
+
+        let short = decode_chi_squared(&encode("Zeus", 5)).expect("has letters");
+        assert!(short.margin < response.margin);
+
+        assert!(matches!(decode_chi_squared("1984"), Err(Error::InvalidArgument(_))));
+    }
+
+    #[test]
+    fn test_decode_chi_squared_russian() {
+        let profile = LanguageProfile::russian();
+        let message = "Мороз и солнце, день чудесный! Ещё ты дремлешь, друг прелестный";
+        let encoded = encode_with(message, 7, profile.alphabet());
+        let response = decode_chi_squared_with(&encoded, &profile).expect("has letters");
+        assert_eq!(7, response.key);
+        assert_eq!(message, response.decoded_message);
+    }
+
+    #[test]
+    fn test_alphabets() {
+        let cyrillic = Alphabet::cyrillic();
+        assert_eq!("Тулезх, плу!", encode_with("Привет, мир!", 3, &cyrillic));
+        assert_eq!("Привет, мир!", decode_with("Тулезх, плу!", 3, &cyrillic));
+
+        let alphanumeric = Alphabet::alphanumeric();
+        assert_eq!("Agent 1 to 9", decode_with(&encode_with("Agent 1 to 9", 30, &alphanumeric), 30, &alphanumeric));
+        assert_eq!("3AB", encode_with("Zwx", 4, &alphanumeric));
+    }
+
+    #[test]
+    fn test_foreign_letters() {
+        let letters = Alphabet::ascii_letters();
+        assert_eq!("Vwudßh fdié", encode("Straße café", 3));
+        assert_eq!("Straße café", decode("Vwudßh fdié", 3));
+        assert!(matches!(
+            encode_with_policy("Straße café", 3, &letters, ForeignCharPolicy::Reject),
+            Err(Error::ForeignCharacter(ForeignCharError { ch: 'ß', position: 4 }))
+        ));
+        assert_eq!(
+            "Vwudvvh fdih",
+            encode_with_policy("Straße café", 3, &letters, ForeignCharPolicy::Transliterate).expect("transliterate")
+        );
+
+        let cipher = Caesar::new(3).expect("valid key").with_policy(ForeignCharPolicy::Reject);
+        assert!(cipher.encrypt("café").is_err());
+        assert_eq!("fdih", cipher.encrypt("cafe").expect("ascii only"));
+    }
+
+    #[test]
+    fn test_round_trip_arbitrary_unicode() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let alphabets = [Alphabet::ascii_letters(), Alphabet::latin(), Alphabet::cyrillic(), Alphabet::printable_ascii()];
+        for _ in 0..200 {
+            let message = crate::alphabet::random_unicode(&mut rng, 40);
+            let key = rng.random_range(0..100);
+            for alphabet in &alphabets {
+                let pass_through = ForeignCharPolicy::PassThrough;
+                let encoded = encode_with_policy(&message, key, alphabet, pass_through).expect("never fails");
+                assert_eq!(message, decode_with_policy(&encoded, key, alphabet, pass_through).expect("never fails"));
+
+                let transliterate = ForeignCharPolicy::Transliterate;
+                let expected = transliterate.prepare(&message, alphabet).expect("never fails");
+                let encoded = encode_with_policy(&message, key, alphabet, transliterate).expect("never fails");
+                assert_eq!(expected, decode_with_policy(&encoded, key, alphabet, transliterate).expect("never fails"));
+
+                let reject = ForeignCharPolicy::Reject;
+                if let Ok(encoded) = encode_with_policy(&message, key, alphabet, reject) {
+                    assert_eq!(message, decode_with_policy(&encoded, key, alphabet, reject).expect("same letters"));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_cipher_trait() {
+        let cipher = Caesar::new(6).expect("valid key");
+        assert_eq!(6, *cipher.key());
+        assert_eq!("gggg26QQGHpgnyfd", cipher.encrypt("aaaa26KKABjahszx").expect("encrypt"));
+        assert_eq!("aaaa26KKABjahszx", cipher.decrypt("gggg26QQGHpgnyfd").expect("decrypt"));
+    }
 }
 
 
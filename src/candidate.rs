@@ -0,0 +1,51 @@
+use std::cmp::Ordering;
+
+/// One possible decryption found by a cracker, with how plausible it looks.
+/// Higher scores are better; what the score measures depends on the cracker.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candidate<K> {
+    pub key: K,
+    pub decoded_message: String,
+    pub score: f64,
+}
+
+impl<K> Candidate<K> {
+    pub fn new(key: K, decoded_message: String, score: f64) -> Self {
+        Self { key, decoded_message, score }
+    }
+}
+
+/// Sorts `candidates` best-first. The sort is stable, so candidates with the
+/// same score keep the order they were tried in.
+pub fn rank<K>(candidates: &mut [Candidate<K>]) {
+    candidates.sort_by(|a, b| compare(b.score, a.score));
+}
+
+fn compare(a: f64, b: f64) -> Ordering {
+    // NaN never beats a real score
+    match (a.is_nan(), b.is_nan()) {
+        (true, true) => Ordering::Equal,
+        (true, false) => Ordering::Less,
+        (false, true) => Ordering::Greater,
+        (false, false) => a.total_cmp(&b),
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_rank() {
+        let mut candidates = vec![
+            Candidate::new(1, "a".to_string(), 0.5),
+            Candidate::new(2, "b".to_string(), f64::NAN),
+            Candidate::new(3, "c".to_string(), 0.9),
+            Candidate::new(4, "d".to_string(), 0.5),
+        ];
+        rank(&mut candidates);
+        let keys: Vec<i32> = candidates.iter().map(|candidate| candidate.key).collect();
+        assert_eq!(vec![3, 1, 4, 2], keys);
+    }
+}
@@ -0,0 +1,74 @@
+use crate::error::Result;
+
+/// Common interface shared by every cipher in the kit.
+///
+/// A cipher instance owns its key, so code can hold ciphers generically and
+/// swap one implementation for another without caring about the key shape.
+pub trait Cipher {
+    type Key;
+
+    /// Checks that `key` can be used with this cipher.
+    fn validate_key(key: &Self::Key) -> Result<()>;
+
+    fn key(&self) -> &Self::Key;
+
+    fn encrypt(&self, message: &str) -> Result<String>;
+
+    fn decrypt(&self, message: &str) -> Result<String>;
+}
+
+/// Object safe view of a [`Cipher`], used when the concrete cipher is only
+/// known at runtime (see [`crate::registry::CipherRegistry`]).
+pub trait DynCipher {
+    fn encrypt(&self, message: &str) -> Result<String>;
+
+    fn decrypt(&self, message: &str) -> Result<String>;
+}
+
+impl<C: Cipher> DynCipher for C {
+    fn encrypt(&self, message: &str) -> Result<String> {
+        Cipher::encrypt(self, message)
+    }
+
+    fn decrypt(&self, message: &str) -> Result<String> {
+        Cipher::decrypt(self, message)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::atbash_cipher::Atbash;
+    use crate::caeser_cipher::Caesar;
+    use crate::rot13::Rot13;
+    use crate::vigenere_cipher::Vigenere;
+
+    fn round_trip<C: Cipher>(cipher: &C, message: &str) -> String {
+        let encrypted = cipher.encrypt(message).expect("encrypt");
+        cipher.decrypt(&encrypted).expect("decrypt")
+    }
+
+    #[test]
+    fn test_generic_round_trip() {
+        let message = "Be normal, and the crowd will accept you.";
+        assert_eq!(message, round_trip(&Caesar::new(7).expect("valid key"), message));
+        assert_eq!(message, round_trip(&Vigenere::new("randalthor").expect("valid key"), message));
+        assert_eq!(message, round_trip(&Atbash::new(), message));
+        assert_eq!(message, round_trip(&Rot13::new(), message));
+    }
+
+    #[test]
+    fn test_swap_at_runtime() {
+        let message = "Sally sells sea shells by the seashore.";
+        let ciphers: Vec<Box<dyn DynCipher>> = vec![
+            Box::new(Caesar::new(3).expect("valid key")),
+            Box::new(Atbash::new()),
+        ];
+        for cipher in ciphers {
+            let encrypted = cipher.encrypt(message).expect("encrypt");
+            assert_ne!(message, encrypted);
+            assert_eq!(message, cipher.decrypt(&encrypted).expect("decrypt"));
+        }
+    }
+}
@@ -0,0 +1,340 @@
+use rand::Rng;
+use rand::seq::SliceRandom;
+
+use crate::candidate::{self, Candidate};
+use crate::cipher::Cipher;
+use crate::detection::ngram::NgramScorer;
+use crate::error::{Error, Result};
+
+// widths up to this are searched exhaustively, 7! = 5040 orders
+const EXHAUSTIVE_WIDTH: usize = 7;
+
+/// How the last row of the grid is completed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Padding {
+    /// Leave it short, giving some columns one character fewer (irregular
+    /// columnar transposition). Nothing is added to the message.
+    #[default]
+    Irregular,
+    /// Fill it with the character so every column has the same length
+    /// (complete columnar transposition). The padding is left in the
+    /// decrypted text.
+    Complete(char),
+}
+
+/// The column order a keyword stands for: the rank of each letter in
+/// alphabetical order, repeated letters ranked left to right. `"zebras"`
+/// gives `[5, 2, 1, 3, 0, 4]`, so the column under `a` is read first.
+pub fn column_order(keyword: &str) -> Result<Vec<usize>> {
+    let keyword: Vec<char> = keyword.chars().filter(|ch| !ch.is_whitespace()).flat_map(char::to_lowercase).collect();
+    if keyword.is_empty() {
+        return Err(Error::InvalidKey("transposition keyword must not be empty".to_string()));
+    }
+    let mut columns: Vec<usize> = (0..keyword.len()).collect();
+    columns.sort_by_key(|&column| keyword[column]);
+    let mut order = vec![0; keyword.len()];
+    for (rank, column) in columns.into_iter().enumerate() {
+        order[column] = rank;
+    }
+    Ok(order)
+}
+
+
+/// Writes the message in rows under `keyword` and reads it off column by
+/// column in the keyword's [`column_order`], irregular columns.
+pub fn encode(message: &str, keyword: &str) -> Result<String> {
+    encode_with_order(message, &column_order(keyword)?, Padding::Irregular)
+}
+
+
+pub fn decode(message: &str, keyword: &str) -> Result<String> {
+    decode_with_order(message, &column_order(keyword)?)
+}
+
+
+/// Like [`encode`] with the column order given directly. Every character is
+/// transposed, including spaces and punctuation.
+pub fn encode_with_order(message: &str, order: &[usize], padding: Padding) -> Result<String> {
+    check_order(order)?;
+    let mut chars: Vec<char> = message.chars().collect();
+    if let Padding::Complete(pad) = padding {
+        while !chars.len().is_multiple_of(order.len()) {
+            chars.push(pad);
+        }
+    }
+    Ok(positions(chars.len(), order).into_iter().map(|i| chars[i]).collect())
+}
+
+
+/// Undoes [`encode_with_order`], whichever padding was used.
+pub fn decode_with_order(message: &str, order: &[usize]) -> Result<String> {
+    check_order(order)?;
+    Ok(undo(&message.chars().collect::<Vec<char>>(), order).into_iter().collect())
+}
+
+
+/// Ranks column orders for every width from 2 to `max_width` by quadgram
+/// fitness, using the thread local generator, see [`crack_with_rng`].
+pub fn crack(message: &str, max_width: usize) -> Result<Vec<Candidate<Vec<usize>>>> {
+    crack_with_rng(message, max_width, &mut rand::rng())
+}
+
+
+/// Finds the best column order for every width from 2 to `max_width`: widths
+/// up to 7 by trying every order, wider ones by hill-climbing on swaps of two
+/// columns from random orders. Returns one candidate per width, best first,
+/// scored by the quadgram fitness of the decryption.
+pub fn crack_with_rng<R: Rng + ?Sized>(message: &str, max_width: usize, rng: &mut R) -> Result<Vec<Candidate<Vec<usize>>>> {
+    let scorer = NgramScorer::english_quadgrams()?;
+    let chars: Vec<char> = message.chars().collect();
+    let max_width = max_width.min(chars.len());
+    if max_width < 2 {
+        return Err(Error::InvalidArgument("need at least two columns and two characters to try".to_string()));
+    }
+    let fitness = |order: &[usize]| scorer.score(&undo(&chars, order).into_iter().collect::<String>());
+
+    let mut candidates = Vec::new();
+    for width in 2..=max_width {
+        let (order, score) = if width <= EXHAUSTIVE_WIDTH {
+            exhaustive(width, &fitness)
+        } else {
+            climb(width, &fitness, rng)
+        };
+        let decoded = undo(&chars, &order).into_iter().collect();
+        candidates.push(Candidate::new(order, decoded, score));
+    }
+    candidate::rank(&mut candidates);
+    Ok(candidates)
+}
+
+fn exhaustive(width: usize, fitness: &impl Fn(&[usize]) -> f64) -> (Vec<usize>, f64) {
+    let mut order: Vec<usize> = (0..width).collect();
+    let mut best = (order.clone(), fitness(&order));
+    while next_permutation(&mut order) {
+        let score = fitness(&order);
+        if score > best.1 {
+            best = (order.clone(), score);
+        }
+    }
+    best
+}
+
+// moves: swapping two columns, or taking one out and putting it back elsewhere
+fn climb<R: Rng + ?Sized>(width: usize, fitness: &impl Fn(&[usize]) -> f64, rng: &mut R) -> (Vec<usize>, f64) {
+    let mut best: Option<(Vec<usize>, f64)> = None;
+    for _ in 0..4 * width {
+        let mut order: Vec<usize> = (0..width).collect();
+        order.shuffle(rng);
+        let mut score = fitness(&order);
+        let mut improved = true;
+        while improved {
+            improved = false;
+            for a in 0..width {
+                for b in 0..width {
+                    if a == b {
+                        continue;
+                    }
+                    let mut candidate = order.clone();
+                    if a < b {
+                        candidate.swap(a, b);
+                    } else {
+                        let column = candidate.remove(a);
+                        candidate.insert(b, column);
+                    }
+                    let candidate_score = fitness(&candidate);
+                    if candidate_score > score {
+                        (order, score) = (candidate, candidate_score);
+                        improved = true;
+                    }
+                }
+            }
+        }
+        if best.as_ref().is_none_or(|(_, best)| score > *best) {
+            best = Some((order, score));
+        }
+    }
+    best.expect("at least one climb")
+}
+
+// lexicographic successor, false after the last permutation
+fn next_permutation(order: &mut [usize]) -> bool {
+    let Some(i) = (1..order.len()).rev().find(|&i| order[i - 1] < order[i]) else {
+        return false;
+    };
+    let j = (i..order.len()).rev().find(|&j| order[j] > order[i - 1]).expect("order[i] qualifies");
+    order.swap(i - 1, j);
+    order[i..].reverse();
+    true
+}
+
+fn check_order(order: &[usize]) -> Result<()> {
+    let mut seen = vec![false; order.len()];
+    for &rank in order {
+        if rank >= order.len() || seen[rank] {
+            return Err(Error::InvalidKey(format!("column order must be a permutation of 0 to {}, got {:?}", order.len(), order)));
+        }
+        seen[rank] = true;
+    }
+    if order.is_empty() {
+        return Err(Error::InvalidKey("column order must not be empty".to_string()));
+    }
+    Ok(())
+}
+
+// positions of the message in the order the columns are read
+fn positions(length: usize, order: &[usize]) -> Vec<usize> {
+    let width = order.len();
+    let mut columns: Vec<usize> = (0..width).collect();
+    columns.sort_by_key(|&column| order[column]);
+    columns.into_iter().
+        flat_map(|column| (column..length).step_by(width)).
+        collect()
+}
+
+fn undo(chars: &[char], order: &[usize]) -> Vec<char> {
+    let mut decoded = vec![' '; chars.len()];
+    for (&ch, i) in chars.iter().zip(positions(chars.len(), order)) {
+        decoded[i] = ch;
+    }
+    decoded
+}
+
+/// Keyed columnar transposition, see [`encode`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Columnar {
+    key: String,
+    order: Vec<usize>,
+    padding: Padding,
+}
+
+impl Columnar {
+    pub fn new(keyword: &str) -> Result<Self> {
+        Ok(Self { key: keyword.to_string(), order: column_order(keyword)?, padding: Padding::default() })
+    }
+
+    pub fn with_padding(mut self, padding: Padding) -> Self {
+        self.padding = padding;
+        self
+    }
+}
+
+impl Cipher for Columnar {
+    type Key = String;
+
+    fn validate_key(key: &String) -> Result<()> {
+        column_order(key).map(|_| ())
+    }
+
+    fn key(&self) -> &String {
+        &self.key
+    }
+
+    fn encrypt(&self, message: &str) -> Result<String> {
+        encode_with_order(message, &self.order, self.padding)
+    }
+
+    fn decrypt(&self, message: &str) -> Result<String> {
+        decode_with_order(message, &self.order)
+    }
+}
+
+/// Two columnar transpositions in a row, usually with different keywords.
+/// Padding only applies to the first one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DoubleTransposition {
+    key: (String, String),
+    first: Columnar,
+    second: Columnar,
+}
+
+impl DoubleTransposition {
+    pub fn new(first: &str, second: &str) -> Result<Self> {
+        Ok(Self {
+            key: (first.to_string(), second.to_string()),
+            first: Columnar::new(first)?,
+            second: Columnar::new(second)?,
+        })
+    }
+
+    pub fn with_padding(mut self, padding: Padding) -> Self {
+        self.first = self.first.with_padding(padding);
+        self
+    }
+}
+
+impl Cipher for DoubleTransposition {
+    type Key = (String, String);
+
+    fn validate_key(key: &(String, String)) -> Result<()> {
+        Columnar::validate_key(&key.0)?;
+        Columnar::validate_key(&key.1)
+    }
+
+    fn key(&self) -> &(String, String) {
+        &self.key
+    }
+
+    fn encrypt(&self, message: &str) -> Result<String> {
+        self.second.encrypt(&self.first.encrypt(message)?)
+    }
+
+    fn decrypt(&self, message: &str) -> Result<String> {
+        self.first.decrypt(&self.second.decrypt(message)?)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    use super::*;
+
+    #[test]
+    fn test_column_order() {
+        assert_eq!(vec![5, 2, 1, 3, 0, 4], column_order("zebras").expect("valid keyword"));
+        assert_eq!(vec![0, 1, 2], column_order("AaA").expect("valid keyword"));
+        assert!(matches!(column_order(" "), Err(Error::InvalidKey(_))));
+        assert!(matches!(encode_with_order("text", &[0, 2], Padding::Irregular), Err(Error::InvalidKey(_))));
+    }
+
+    #[test]
+    fn test_encode() {
+        let message = "WEAREDISCOVEREDFLEEATONCE";
+        assert_eq!("EVLNACDTESEAROFODEECWIREE", encode(message, "zebras").expect("valid keyword"));
+        assert_eq!(message, decode("EVLNACDTESEAROFODEECWIREE", "zebras").expect("valid keyword"));
+
+        let complete = Columnar::new("zebras").expect("valid keyword").with_padding(Padding::Complete('Q'));
+        let encoded = complete.encrypt(message).expect("encrypt");
+        assert_eq!("EVLNQACDTQESEAQROFOQDEECQWIREE", encoded);
+        assert_eq!(format!("{}QQQQQ", message), complete.decrypt(&encoded).expect("decrypt"));
+    }
+
+    #[test]
+    fn test_double_transposition() {
+        let cipher = DoubleTransposition::new("zebras", "stripe").expect("valid keywords");
+        let message = "We are discovered, flee at once!";
+        let encoded = cipher.encrypt(message).expect("encrypt");
+        assert_ne!(encode(message, "zebras").expect("valid keyword"), encoded);
+        assert_eq!(message, cipher.decrypt(&encoded).expect("decrypt"));
+
+        let padded = cipher.clone().with_padding(Padding::Complete('x'));
+        assert_eq!(format!("{}xxxx", message), padded.decrypt(&padded.encrypt(message).expect("encrypt")).expect("decrypt"));
+    }
+
+    #[test]
+    fn test_crack() {
+        let message = "the enemy will attack the northern bridge at dawn so move the reserves across the river tonight";
+        let encoded = encode(message, "cargo").expect("valid keyword");
+        let candidates = crack_with_rng(&encoded, 10, &mut StdRng::seed_from_u64(2)).expect("long enough");
+        assert_eq!(9, candidates.len());
+        assert_eq!(column_order("cargo").expect("valid keyword"), candidates[0].key);
+        assert_eq!(message, candidates[0].decoded_message);
+
+        // too wide to try every order
+        let encoded = encode(message, "blueprints").expect("valid keyword");
+        let candidates = crack_with_rng(&encoded, 10, &mut StdRng::seed_from_u64(2)).expect("long enough");
+        assert_eq!(message, candidates[0].decoded_message);
+    }
+}
@@ -1,4 +1,14 @@
 use crate::detection::trie::TrieIterator;
+use crate::error::{Error, Result};
+
+/// The bundled dictionary.
+pub(crate) const TRIE_DB: &str = "src/detection/trie_db/trie_data.json.gz";
+
+// the dictionary has every letter and most pairs of letters, only these are trusted as words
+pub(crate) const SHORT_WORDS: &[&str] = &[
+    "a", "i", "am", "an", "as", "at", "be", "by", "do", "go", "he", "if", "in", "is", "it", "me",
+    "my", "no", "of", "oh", "on", "or", "so", "to", "up", "us", "we",
+];
 
 pub struct Detector {
     trie: crate::detection::trie::Trie,
@@ -7,19 +17,22 @@ pub struct Detector {
 
 
 impl Detector {
-    pub fn new(trie_gz_path: &str) -> anyhow::Result<Self> {
+    pub fn new(trie_gz_path: &str) -> Result<Self> {
         let trie = crate::detection::trie::Trie::from_json_file(trie_gz_path)?;
         Ok(Self { trie, threshold: 0.85 })
     }
 
-    pub fn new_with_fix_db() -> anyhow::Result<Self> {
-        let trie = crate::detection::trie::Trie::from_json_file("src/detection/trie_db/trie_data.json.gz")?;
+    pub fn new_with_fix_db() -> Result<Self> {
+        let trie = crate::detection::trie::Trie::from_json_file(TRIE_DB)?;
         Ok(Self { trie, threshold: 0.85 })
     }
 
 
-    pub fn set_threshold(&mut self, threshold: f64) -> anyhow::Result<()> {
-        anyhow::ensure!(threshold <= 1.0 && threshold > 0.0, "threshold should be greater than 0 and less than or equal to 1");
+    pub fn set_threshold(&mut self, threshold: f64) -> Result<()> {
+        if !(threshold <= 1.0 && threshold > 0.0) {
+            return Err(Error::InvalidArgument("threshold should be greater than 0 and less than or equal to 1".to_string()));
+        }
+        self.threshold = threshold;
         Ok(())
     }
 
@@ -39,7 +52,77 @@ impl Detector {
     }
 
 
-    pub fn iter_dictionary_words(&self) -> TrieIterator {
+    /// English-likeness between 0 and 1: the share of letters that belong to
+    /// dictionary words. Unlike [`Detector::is_english`] this gives partial
+    /// credit, so candidate decryptions can be ranked against each other.
+    pub fn score(&self, text: &str) -> f64 {
+        let mut matched_letters = 0;
+        let mut total_letters = 0;
+        for word in text.split_whitespace() {
+            let word: String = word.chars().filter(|ch|
+                ch.is_alphabetic()).collect();
+            let letters = word.chars().count();
+            total_letters += letters;
+            if letters > 0 && self.trie.contains(&word) {
+                matched_letters += letters;
+            }
+        }
+        if total_letters == 0 {
+            return 0.0;
+        }
+        matched_letters as f64 / total_letters as f64
+    }
+
+
+    /// Re-inserts word breaks into text whose spaces were stripped, e.g. the
+    /// output of a classical cipher, choosing the split into the fewest
+    /// dictionary words. Letters that fit no word are kept together as one
+    /// unknown word, so gibberish does not pass [`Detector::is_english`].
+    pub fn segment(&self, text: &str) -> String {
+        let chars: Vec<char> = text.chars().filter(|ch| !ch.is_whitespace()).collect();
+        // best[i] is the cheapest split of chars[i..] as (cost, length of its first word or 0 for an unknown letter)
+        let mut best = vec![(0usize, 0usize); chars.len() + 1];
+        for i in (0..chars.len()).rev() {
+            // an unknown letter costs as much as two words
+            best[i] = (best[i + 1].0 + 2, 0);
+            for length in self.trie.prefix_lengths(chars[i..].iter().copied()) {
+                // the dictionary has every letter and most pairs of letters, only trust common short words
+                if length < 3 && !SHORT_WORDS.contains(&chars[i..i + length].iter().collect::<String>().to_ascii_lowercase().as_str()) {
+                    continue;
+                }
+                if best[i + length].0 + 1 < best[i].0 {
+                    best[i] = (best[i + length].0 + 1, length);
+                }
+            }
+        }
+
+        let mut words: Vec<String> = Vec::new();
+        let mut unknown = String::new();
+        let mut i = 0;
+        while i < chars.len() {
+            match best[i].1 {
+                0 => {
+                    unknown.push(chars[i]);
+                    i += 1;
+                }
+                length => {
+                    if !unknown.is_empty() {
+                        words.push(std::mem::take(&mut unknown));
+                    }
+                    words.push(chars[i..i + length].iter().collect());
+                    i += length;
+                }
+            }
+        }
+        if !unknown.is_empty() {
+            words.push(unknown);
+        }
+        words.join(" ")
+    }
+
+
+    /// The dictionary in alphabetical order.
+    pub fn iter_dictionary_words(&self) -> TrieIterator<'_> {
         self.trie.iter_words()
     }
 }
@@ -52,20 +135,40 @@ mod test {
     #[test]
     fn test_is_english() {
         let detector = Detector::new("src/detection/trie_db/trie_data.json.gz").expect("valid detector");
-        assert_eq!(true, detector.is_english("hello world"));
+        assert!(detector.is_english("hello world"));
 
         let sample: &str = "On offering to help the blind man, the man who then stole his car, had not, at that precise moment,
   had any evil intention, quite the contrary, what he did was nothing more than obey those feelings of generosity and altruism which,
   as everyone knows, are the two best traits of human nature and to be found in much more hardened criminals than this one,
   a simple car-thief without any hope of advancing in his profession, exploited by the real owners of this enterprise,
   for it is they who take advantage of the needs of the poor.";
-        assert_eq!(true, detector.is_english(sample));
+        assert!(detector.is_english(sample));
 
         let spanish_sample: &str = "Al ofrecerse a ayudar al ciego, el hombre que luego le robó el coche, en ese preciso momento, no había
   tenía alguna mala intención, todo lo contrario, lo que hizo no fue más que obedecer a esos sentimientos de generosidad y altruismo que,
   como todo el mundo sabe, son los dos mejores rasgos de la naturaleza humana y se encuentran en criminales mucho más empedernidos que éste,
   un simple ladrón de coches sin esperanzas de progresar en su profesión, explotado por los verdaderos dueños de esta empresa,
   porque son ellos quienes se aprovechan de las necesidades de los pobres.";
-        assert_eq!(false, detector.is_english(spanish_sample));
+        assert!(!detector.is_english(spanish_sample));
+    }
+
+    #[test]
+    fn test_segment() {
+        let detector = Detector::new_with_fix_db().expect("valid detector");
+        assert_eq!("the quick brown fox", detector.segment("thequickbrownfox"));
+        assert!(detector.is_english(&detector.segment("meetmeatthebridgeatmidnight")));
+        assert!(!detector.is_english(&detector.segment("qxvbzkjwpfgmhrtlyncsdqxvjzwkbpf")));
+        assert!(!detector.is_english(&detector.segment("wkhtxlfneurzqiramxpsvryhuwkhodcbgrj")));
+        assert_eq!("", detector.segment(""));
+    }
+
+    #[test]
+    fn test_score() {
+        let detector = Detector::new_with_fix_db().expect("valid detector");
+        assert_eq!(1.0, detector.score("Hello, world!"));
+        assert_eq!(0.0, detector.score("Xyzzy qwrtp"));
+        assert_eq!(0.0, detector.score("42 ..."));
+        // "hello" counts for its 5 letters, the gibberish for its 3
+        assert_eq!(5.0 / 8.0, detector.score("hello xqz"));
     }
 }
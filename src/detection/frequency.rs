@@ -0,0 +1,126 @@
+use crate::alphabet::Alphabet;
+use crate::error::{Error, Result};
+
+// relative letter frequencies in percent, a to z
+const ENGLISH: [f64; 26] = [
+    8.167, 1.492, 2.782, 4.253, 12.702, 2.228, 2.015, 6.094, 6.966, 0.153, 0.772, 4.025, 2.406,
+    6.749, 7.507, 1.929, 0.095, 5.987, 6.327, 9.056, 2.758, 0.978, 2.360, 0.150, 1.974, 0.074,
+];
+
+// а to я in the order of `Alphabet::cyrillic`
+const RUSSIAN: [f64; 33] = [
+    8.01, 1.59, 4.54, 1.70, 2.98, 8.45, 0.04, 0.94, 1.65, 7.35, 1.21, 3.49, 4.40, 3.21, 6.70, 10.97, 2.81,
+    4.73, 5.47, 6.26, 2.62, 0.26, 0.97, 0.48, 1.44, 0.73, 0.36, 0.04, 1.90, 1.74, 0.32, 0.64, 2.01,
+];
+
+/// How often each letter of an alphabet occurs in ordinary text of a language.
+/// Used to score candidate decryptions statistically, without a dictionary.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LanguageProfile {
+    alphabet: Alphabet,
+    frequencies: Vec<f64>,
+}
+
+impl LanguageProfile {
+    pub fn english() -> Self {
+        Self::new(Alphabet::ascii_letters(), &ENGLISH).expect("valid english profile")
+    }
+
+    pub fn russian() -> Self {
+        Self::new(Alphabet::cyrillic(), &RUSSIAN).expect("valid russian profile")
+    }
+
+    /// `frequencies` gives the relative frequency of every symbol of `alphabet`
+    /// in order; they are normalised so they need not add up to 1.
+    pub fn new(alphabet: Alphabet, frequencies: &[f64]) -> Result<Self> {
+        if frequencies.len() != alphabet.len() {
+            return Err(Error::InvalidArgument(format!(
+                "expected {} frequencies, one per symbol, got {}", alphabet.len(), frequencies.len()
+            )));
+        }
+        // a zero expected count would make the chi-squared statistic infinite
+        if frequencies.iter().any(|f| !f.is_finite() || *f <= 0.0) {
+            return Err(Error::InvalidArgument("frequencies must be positive".to_string()));
+        }
+        let total: f64 = frequencies.iter().sum();
+        Ok(Self { alphabet, frequencies: frequencies.iter().map(|f| f / total).collect() })
+    }
+
+    pub fn alphabet(&self) -> &Alphabet {
+        &self.alphabet
+    }
+
+    /// Expected share of each symbol, summing to 1.
+    pub fn frequencies(&self) -> &[f64] {
+        &self.frequencies
+    }
+
+    /// Chance that two letters drawn at random from text in the language are
+    /// the same, about 0.066 for English against 0.038 for uniformly random letters.
+    pub fn index_of_coincidence(&self) -> f64 {
+        self.frequencies.iter().map(|f| f * f).sum()
+    }
+
+    /// How many times each symbol of the alphabet occurs in `text`; anything
+    /// else (spaces, punctuation, foreign letters) is ignored.
+    pub fn count(&self, text: &str) -> Vec<usize> {
+        let mut counts = vec![0; self.alphabet.len()];
+        for ch in text.chars() {
+            if let Some(index) = self.alphabet.index_of(ch) {
+                counts[index] += 1;
+            }
+        }
+        counts
+    }
+
+    /// Chi-squared distance between the letters of `text` and the profile.
+    /// Lower is closer; English text usually scores well under 100.
+    pub fn chi_squared(&self, text: &str) -> f64 {
+        self.chi_squared_counts(&self.count(text))
+    }
+
+    /// [`LanguageProfile::chi_squared`] over counts from [`LanguageProfile::count`].
+    /// Returns infinity when there is nothing to compare.
+    pub fn chi_squared_counts(&self, counts: &[usize]) -> f64 {
+        let total: usize = counts.iter().sum();
+        if total == 0 {
+            return f64::INFINITY;
+        }
+        counts.iter().zip(&self.frequencies).
+            map(|(&observed, &frequency)| {
+                let expected = frequency * total as f64;
+                (observed as f64 - expected).powi(2) / expected
+            }).
+            sum()
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_chi_squared() {
+        let english = LanguageProfile::english();
+        let plain = english.chi_squared("Defend the east wall of the castle");
+        let shifted = english.chi_squared("Efgfoe uif fbtu xbmm pg uif dbtumf");
+        assert!(plain < shifted, "{} should be below {}", plain, shifted);
+        assert_eq!(plain, english.chi_squared("DEFENDTHEEASTWALLOFTHECASTLE"));
+        assert_eq!(f64::INFINITY, english.chi_squared("1234 !?"));
+
+        let russian = LanguageProfile::russian();
+        assert!(russian.chi_squared("Москва никогда не спит") < russian.chi_squared("Прсндж рлнсжзг рз фукх"));
+    }
+
+    #[test]
+    fn test_new() {
+        let profile = LanguageProfile::new(Alphabet::custom("ab").expect("valid alphabet"), &[3.0, 1.0]).expect("valid");
+        assert_eq!(&[0.75, 0.25], profile.frequencies());
+        assert_eq!(vec![2, 1], profile.count("a b a c"));
+
+        let letters = Alphabet::ascii_letters();
+        assert!(matches!(LanguageProfile::new(letters.clone(), &[1.0; 25]), Err(Error::InvalidArgument(_))));
+        assert!(matches!(LanguageProfile::new(letters, &[0.0; 26]), Err(Error::InvalidArgument(_))));
+    }
+}
@@ -0,0 +1,203 @@
+use std::collections::HashMap;
+
+use crate::alphabet::Alphabet;
+use crate::detection::frequency::LanguageProfile;
+use crate::error::{Error, Result};
+
+/// A candidate length of a periodic (Vigenere style) key. Higher scores are
+/// more likely; what the score measures depends on the test that produced it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyLength {
+    pub length: usize,
+    pub score: f64,
+}
+
+/// Ranks key lengths from 1 to `max_length` by combining the Kasiski
+/// examination, the index of coincidence of every column and the Friedman
+/// test. Only letters of `profile`'s alphabet are considered, as those are
+/// the only ones that consume the key.
+pub fn estimate(message: &str, profile: &LanguageProfile, max_length: usize) -> Result<Vec<KeyLength>> {
+    let letters = profile.alphabet().indices(message);
+    if max_length == 0 {
+        return Err(Error::InvalidArgument("max_length must be at least 1".to_string()));
+    }
+    if letters.len() < 2 * max_length {
+        return Err(Error::InvalidArgument(format!(
+            "{} letters are too few to test key lengths up to {}", letters.len(), max_length
+        )));
+    }
+
+    let kasiski: HashMap<usize, f64> = kasiski_letters(&letters, max_length).into_iter().
+        map(|candidate| (candidate.length, candidate.score)).
+        collect();
+    let friedman = friedman_letters(&letters, profile);
+    let random = 1.0 / profile.alphabet().len() as f64;
+    let language = profile.index_of_coincidence();
+
+    let mut candidates: Vec<KeyLength> = (1..=max_length).
+        map(|length| {
+            // 0 for columns that look like random letters, 1 for columns that look like the language
+            let ioc = column_coincidence(&letters, profile.alphabet().len(), length);
+            let closeness = ((ioc - random) / (language - random)).clamp(0.0, 1.0);
+            let friedman = friedman.map_or(0.0, |estimate| 1.0 / (1.0 + (length as f64 - estimate).abs() / estimate));
+            let score = 0.6 * closeness + 0.3 * kasiski[&length] + 0.1 * friedman;
+            // multiples of the key length look just as good, prefer the shortest
+            KeyLength { length, score: score / (1.0 + 0.05 * (length - 1) as f64) }
+        }).
+        collect();
+    rank(&mut candidates);
+    Ok(candidates)
+}
+
+
+/// Kasiski examination: repeated trigrams are usually the same plaintext
+/// enciphered with the same part of the key, so the distances between them
+/// tend to be multiples of the key length. Scores every length from 2 to
+/// `max_length` by the share of distances it divides. Divisors of the key
+/// length score at least as high as the key length itself.
+pub fn kasiski(message: &str, alphabet: &Alphabet, max_length: usize) -> Vec<KeyLength> {
+    let mut candidates = kasiski_letters(&alphabet.indices(message), max_length);
+    candidates.retain(|candidate| candidate.length > 1);
+    rank(&mut candidates);
+    candidates
+}
+
+
+/// Index of coincidence test: split the letters into `length` columns, one
+/// per key letter, and score each length by the average index of coincidence
+/// of its columns. The right length (and its multiples) leaves every column
+/// enciphered with a single shift, which keeps the language's coincidence.
+pub fn coincidence(message: &str, alphabet: &Alphabet, max_length: usize) -> Vec<KeyLength> {
+    let letters = alphabet.indices(message);
+    let mut candidates: Vec<KeyLength> = (1..=max_length).
+        map(|length| KeyLength { length, score: column_coincidence(&letters, alphabet.len(), length) }).
+        collect();
+    rank(&mut candidates);
+    candidates
+}
+
+
+/// Friedman test: estimates the key length from how far the index of
+/// coincidence of the whole message has fallen from the language's towards
+/// that of random letters. Only a rough guess, `None` if the message has too
+/// few letters or looks more random than random text.
+pub fn friedman(message: &str, profile: &LanguageProfile) -> Option<f64> {
+    friedman_letters(&profile.alphabet().indices(message), profile)
+}
+
+
+/// Chance that two letters drawn at random from `counts` are the same.
+pub fn index_of_coincidence(counts: &[usize]) -> f64 {
+    let total: usize = counts.iter().sum();
+    if total < 2 {
+        return 0.0;
+    }
+    let pairs: usize = counts.iter().map(|&count| count * count.saturating_sub(1)).sum();
+    pairs as f64 / (total * (total - 1)) as f64
+}
+
+
+fn kasiski_letters(letters: &[usize], max_length: usize) -> Vec<KeyLength> {
+    let mut last_seen = HashMap::new();
+    let mut distances = Vec::new();
+    for (position, trigram) in letters.windows(3).enumerate() {
+        if let Some(previous) = last_seen.insert(trigram, position) {
+            distances.push(position - previous);
+        }
+    }
+
+    (1..=max_length).
+        map(|length| {
+            let divided = distances.iter().filter(|&&distance| distance % length == 0).count();
+            let score = if distances.is_empty() { 0.0 } else { divided as f64 / distances.len() as f64 };
+            KeyLength { length, score }
+        }).
+        collect()
+}
+
+
+fn column_coincidence(letters: &[usize], alphabet_len: usize, length: usize) -> f64 {
+    let mut columns = vec![vec![0; alphabet_len]; length];
+    for (position, &letter) in letters.iter().enumerate() {
+        columns[position % length][letter] += 1;
+    }
+    columns.iter().map(|counts| index_of_coincidence(counts)).sum::<f64>() / length as f64
+}
+
+
+fn friedman_letters(letters: &[usize], profile: &LanguageProfile) -> Option<f64> {
+    let mut counts = vec![0; profile.alphabet().len()];
+    for &letter in letters {
+        counts[letter] += 1;
+    }
+    let observed = index_of_coincidence(&counts);
+    let random = 1.0 / profile.alphabet().len() as f64;
+    let language = profile.index_of_coincidence();
+    let n = letters.len() as f64;
+    let denominator = (n - 1.0) * observed - n * random + language;
+    if letters.len() < 2 || denominator <= 0.0 {
+        return None;
+    }
+    Some(n * (language - random) / denominator)
+}
+
+
+fn rank(candidates: &mut [KeyLength]) {
+    candidates.sort_by(|a, b| b.score.total_cmp(&a.score));
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::vigenere_cipher::encode;
+
+    const TEXT: &str = "On offering to help the blind man, the man who then stole his car, had not, at that precise moment,
+  had any evil intention, quite the contrary, what he did was nothing more than obey those feelings of generosity and altruism which,
+  as everyone knows, are the two best traits of human nature and to be found in much more hardened criminals than this one,
+  a simple car-thief without any hope of advancing in his profession, exploited by the real owners of this enterprise,
+  for it is they who take advantage of the needs of the poor.";
+
+    #[test]
+    fn test_estimate() {
+        let english = LanguageProfile::english();
+        for key in ["lemon", "cipher", "abc", "randalthor", "kryptos", "wonderland"] {
+            let candidates = estimate(&encode(TEXT, key), &english, 16).expect("long enough");
+            assert_eq!(16, candidates.len());
+            assert_eq!(key.len(), candidates[0].length, "key {:?}: {:?}", key, &candidates[..3]);
+        }
+
+        assert_eq!(1, estimate(TEXT, &english, 16).expect("long enough")[0].length);
+        assert!(matches!(estimate("too short", &english, 16), Err(Error::InvalidArgument(_))));
+    }
+
+    #[test]
+    fn test_kasiski() {
+        let alphabet = Alphabet::ascii_letters();
+        let candidates = kasiski(&encode(TEXT, "cipher"), &alphabet, 12);
+        assert_eq!(11, candidates.len());
+        let best: Vec<usize> = candidates[..3].iter().map(|candidate| candidate.length).collect();
+        assert!(best.contains(&6), "{:?}", candidates);
+        // divisors of the key length divide every distance the key length does
+        let score = |length| candidates.iter().find(|candidate| candidate.length == length).expect("tested").score;
+        assert!(score(2) >= score(6) && score(3) >= score(6));
+
+        assert!(kasiski("abcdefgh", &alphabet, 4).iter().all(|candidate| candidate.score == 0.0));
+    }
+
+    #[test]
+    fn test_coincidence() {
+        let english = LanguageProfile::english();
+        let encoded = encode(TEXT, "lemon");
+        let candidates = coincidence(&encoded, english.alphabet(), 12);
+        assert!([5, 10].contains(&candidates[0].length), "{:?}", candidates);
+
+        let estimate = friedman(&encoded, &english).expect("enough letters");
+        assert!((3.0..8.0).contains(&estimate), "{}", estimate);
+        let plain = friedman(TEXT, &english).expect("enough letters");
+        assert!(plain < 1.5, "{}", plain);
+
+        assert_eq!(0.0, index_of_coincidence(&[1, 0, 0]));
+        assert_eq!(1.0, index_of_coincidence(&[0, 4, 0]));
+    }
+}
@@ -1,2 +1,5 @@
 pub mod trie;
-pub mod detect_english;
\ No newline at end of file
+pub mod detect_english;
+pub mod frequency;
+pub mod key_length;
+pub mod ngram;
\ No newline at end of file
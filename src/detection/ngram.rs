@@ -0,0 +1,226 @@
+use std::collections::BTreeMap;
+use std::io::{BufReader, Read, Write};
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+
+const LETTERS: usize = 26;
+const MAX_N: usize = 4;
+
+static ENGLISH: [OnceCell<NgramScorer>; MAX_N] = [OnceCell::new(), OnceCell::new(), OnceCell::new(), OnceCell::new()];
+
+/// How much a run of letters looks like the language, higher is better. The
+/// letters are alphabet positions (`a` is 0) with everything else stripped,
+/// which is the form the statistical crackers work in.
+pub trait Fitness {
+    fn fitness(&self, letters: &[usize]) -> f64;
+}
+
+/// Occurrences of every n-gram of letters in a corpus, the on disk form of an
+/// [`NgramScorer`]. Only `a` to `z` are counted, case folded, and n-grams run
+/// across spaces and punctuation as classical ciphertexts usually drop them.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NgramCounts {
+    n: usize,
+    /// Number of n-grams in the corpus, including any pruned since.
+    total: u64,
+    counts: BTreeMap<String, u64>,
+}
+
+impl NgramCounts {
+    pub fn from_text(n: usize, text: &str) -> Result<Self> {
+        check_n(n)?;
+        let letters: Vec<char> = text.chars().
+            filter(|ch| ch.is_ascii_alphabetic()).
+            map(|ch| ch.to_ascii_lowercase()).
+            collect();
+        let mut counts = BTreeMap::new();
+        for ngram in letters.windows(n) {
+            *counts.entry(ngram.iter().collect()).or_default() += 1;
+        }
+        let total = letters.len().saturating_sub(n - 1) as u64;
+        Ok(Self { n, total, counts })
+    }
+
+    /// Drops n-grams seen fewer than `min_count` times to keep tables small;
+    /// they are scored like n-grams never seen at all.
+    pub fn prune(&mut self, min_count: u64) {
+        self.counts.retain(|_, count| *count >= min_count);
+    }
+
+    pub fn n(&self) -> usize {
+        self.n
+    }
+
+    pub fn get(&self, ngram: &str) -> u64 {
+        self.counts.get(ngram).copied().unwrap_or(0)
+    }
+
+    pub fn from_json_file(file_path: &str) -> Result<Self> {
+        let f = std::fs::File::open(file_path)?;
+        let reader = BufReader::new(f);
+        let mut decoder = GzDecoder::new(reader);
+        let mut buffer = Vec::new();
+        decoder.read_to_end(&mut buffer).
+            map_err(|e| Error::CorruptDictionary(format!("{}: {}", file_path, e)))?;
+        serde_json::from_slice(&buffer).
+            map_err(|e| Error::CorruptDictionary(format!("{}: {}", file_path, e)))
+    }
+
+    pub fn to_json_file(&self, file_path: &str) -> Result<()> {
+        let json = serde_json::to_string(self).
+            map_err(|e| Error::CorruptDictionary(format!("failed to serialize n-grams: {}", e)))?;
+        let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::best());
+        encoder.write_all(json.as_bytes())?;
+        let buffer = encoder.finish()?;
+        std::fs::write(file_path, buffer)?;
+        Ok(())
+    }
+}
+
+/// Log-probability fitness of text under an n-gram model of the language.
+/// Unlike [`crate::detection::detect_english::Detector`] it needs no word
+/// boundaries, so it works on ciphertexts with the spaces stripped.
+#[derive(Debug, Clone)]
+pub struct NgramScorer {
+    n: usize,
+    // log10 probability of every possible n-gram, indexed in base 26
+    log_probs: Vec<f32>,
+}
+
+impl NgramScorer {
+    /// Scorer over the bundled English tables, `n` from 1 (letter
+    /// frequencies) to 4 (quadgrams). Each table is loaded once and shared.
+    pub fn english(n: usize) -> Result<&'static NgramScorer> {
+        check_n(n)?;
+        ENGLISH[n - 1].get_or_try_init(|| {
+            let name = ["monograms", "bigrams", "trigrams", "quadgrams"][n - 1];
+            NgramScorer::from_json_file(&format!("src/detection/ngram_db/english_{}.json.gz", name))
+        })
+    }
+
+    /// The bundled English quadgrams, the best general purpose fitness.
+    pub fn english_quadgrams() -> Result<&'static NgramScorer> {
+        Self::english(4)
+    }
+
+    pub fn new(counts: &NgramCounts) -> Result<Self> {
+        check_n(counts.n)?;
+        if counts.total == 0 {
+            return Err(Error::CorruptDictionary("n-gram table is empty".to_string()));
+        }
+        let total = counts.total as f64;
+        // n-grams never seen get a probability well below anything that was
+        let floor = (0.01 / total).log10() as f32;
+        let mut log_probs = vec![floor; LETTERS.pow(counts.n as u32)];
+        for (ngram, &count) in &counts.counts {
+            if ngram.len() != counts.n || !ngram.bytes().all(|b| b.is_ascii_lowercase()) {
+                return Err(Error::CorruptDictionary(format!("invalid {}-gram {:?}", counts.n, ngram)));
+            }
+            let index = ngram.bytes().fold(0, |index, b| index * LETTERS + (b - b'a') as usize);
+            log_probs[index] = (count as f64 / total).log10() as f32;
+        }
+        Ok(Self { n: counts.n, log_probs })
+    }
+
+    pub fn from_json_file(file_path: &str) -> Result<Self> {
+        Self::new(&NgramCounts::from_json_file(file_path)?)
+    }
+
+    pub fn n(&self) -> usize {
+        self.n
+    }
+
+    /// Fitness of the ASCII letters of `text`, ignoring case and everything else.
+    pub fn score(&self, text: &str) -> f64 {
+        let letters: Vec<usize> = text.chars().
+            filter(|ch| ch.is_ascii_alphabetic()).
+            map(|ch| (ch.to_ascii_lowercase() as u8 - b'a') as usize).
+            collect();
+        self.fitness(&letters)
+    }
+
+    /// Average fitness per n-gram, comparable between texts of different
+    /// lengths. With the bundled quadgrams English text scores around -4.5,
+    /// random letters -7 or below.
+    pub fn score_per_ngram(&self, text: &str) -> f64 {
+        let letters = text.chars().filter(|ch| ch.is_ascii_alphabetic()).count();
+        if letters < self.n {
+            return f64::NEG_INFINITY;
+        }
+        self.score(text) / (letters - self.n + 1) as f64
+    }
+}
+
+impl Fitness for NgramScorer {
+    fn fitness(&self, letters: &[usize]) -> f64 {
+        letters.windows(self.n).
+            map(|ngram| {
+                let index = ngram.iter().fold(0, |index, &letter| index * LETTERS + letter);
+                self.log_probs[index] as f64
+            }).
+            sum()
+    }
+}
+
+fn check_n(n: usize) -> Result<()> {
+    if !(1..=MAX_N).contains(&n) {
+        return Err(Error::InvalidArgument(format!("n-grams must be 1 to {} letters long, got {}", MAX_N, n)));
+    }
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_counts() {
+        let counts = NgramCounts::from_text(2, "The theme, then.").expect("valid n");
+        assert_eq!(2, counts.n());
+        assert_eq!(3, counts.get("th"));
+        assert_eq!(2, counts.get("et"));
+        assert_eq!(0, counts.get("zz"));
+
+        assert!(matches!(NgramCounts::from_text(5, "text"), Err(Error::InvalidArgument(_))));
+        assert!(matches!(NgramScorer::english(0), Err(Error::InvalidArgument(_))));
+    }
+
+    #[test]
+    fn test_english() {
+        let english = "Attack the east wall of the castle at dawn";
+        let shuffled = "Twan xqk tltfj eaa lhcke xttc osa wvtl ahte";
+        for n in 1..=4 {
+            let scorer = NgramScorer::english(n).expect("bundled table");
+            assert_eq!(n, scorer.n());
+            assert!(scorer.score(english) > scorer.score(shuffled), "{}-grams", n);
+        }
+
+        let quadgrams = NgramScorer::english_quadgrams().expect("bundled table");
+        assert_eq!(quadgrams.score(english), quadgrams.score("ATTACKTHEEASTWALLOFTHECASTLEATDAWN"));
+        assert!(quadgrams.score_per_ngram(english) > -5.5);
+        assert!(quadgrams.score_per_ngram("qxzjvkwqpfzxjqvbzkwx") < -7.0);
+        assert_eq!(f64::NEG_INFINITY, quadgrams.score_per_ngram("abc"));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut counts = NgramCounts::from_text(3, "the cat sat on the mat with the hat").expect("valid n");
+        counts.prune(2);
+        assert_eq!(0, counts.get("cat"));
+        assert!(counts.get("the") >= 3);
+
+        let path = std::env::temp_dir().join(format!("ngram-test-{}.json.gz", std::process::id()));
+        let path = path.to_str().expect("utf-8 temp dir");
+        counts.to_json_file(path).expect("write");
+        assert_eq!(counts, NgramCounts::from_json_file(path).expect("read"));
+        std::fs::write(path, b"not gzip").expect("write");
+        assert!(matches!(NgramScorer::from_json_file(path), Err(Error::CorruptDictionary(_))));
+        std::fs::remove_file(path).expect("cleanup");
+    }
+}
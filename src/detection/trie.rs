@@ -1,10 +1,12 @@
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::io::{BufReader, Read, Write};
 
 use flate2::read::GzDecoder;
 use flate2::write::GzEncoder;
 use serde::{Deserialize, Serialize};
 
+use crate::error::{Error, Result};
+
 #[derive(Default, Debug, Serialize, Deserialize)]
 pub struct Trie {
     root: TrieNode,
@@ -12,9 +14,10 @@ pub struct Trie {
 
 #[derive(Default, Debug, Serialize, Deserialize, Clone)]
 struct TrieNode {
-    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    // ordered, so the words are always listed in the same (alphabetical) order
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
     #[serde(default)]
-    children: HashMap<char, TrieNode>,
+    children: BTreeMap<char, TrieNode>,
     is_end_of_word: bool,
 }
 
@@ -26,7 +29,7 @@ impl Trie {
     pub fn insert(&mut self, word: &str) {
         let mut current = &mut self.root;
         for ch in word.to_ascii_lowercase().chars() {
-            current = current.children.entry(ch).or_insert(TrieNode::default());
+            current = current.children.entry(ch).or_default();
         }
         current.is_end_of_word = true;
     }
@@ -43,6 +46,54 @@ impl Trie {
         current.is_end_of_word
     }
 
+    /// Lengths, in chars, of every word that `text` starts with, shortest
+    /// first. Only walks `text` as far as some word matches it.
+    pub fn prefix_lengths<I: IntoIterator<Item = char>>(&self, text: I) -> Vec<usize> {
+        let mut lengths = Vec::new();
+        let mut current = &self.root;
+        for (i, ch) in text.into_iter().enumerate() {
+            match current.children.get(&ch.to_ascii_lowercase()) {
+                Some(node) => current = node,
+                None => break,
+            }
+            if current.is_end_of_word {
+                lengths.push(i + 1);
+            }
+        }
+        lengths
+    }
+
+    /// Whether some word starts with `prefix`, or is `prefix` itself.
+    pub fn contains_prefix<I: IntoIterator<Item = char>>(&self, prefix: I) -> bool {
+        let mut current = &self.root;
+        for ch in prefix {
+            match current.children.get(&ch.to_ascii_lowercase()) {
+                Some(node) => current = node,
+                None => return false,
+            }
+        }
+        true
+    }
+
+    /// Trie of every word spelled backwards, for matching word endings.
+    pub fn reversed(&self) -> Trie {
+        let mut reversed = Trie::new();
+        let mut word = String::new();
+        Self::reverse_recursive(&self.root, &mut word, &mut reversed);
+        reversed
+    }
+
+    fn reverse_recursive(node: &TrieNode, prefix: &mut String, reversed: &mut Trie) {
+        for (ch, child) in &node.children {
+            prefix.push(*ch);
+            if child.is_end_of_word {
+                reversed.insert(&prefix.chars().rev().collect::<String>());
+            }
+            Self::reverse_recursive(child, prefix, reversed);
+            prefix.pop();
+        }
+    }
+
     fn display_words(&self) {
         let mut word = String::new();
         self.display_words_recursive(&self.root, &mut word);
@@ -59,27 +110,30 @@ impl Trie {
         }
     }
 
-    pub fn iter_words(&self) -> TrieIterator {
+    /// Every word in alphabetical order.
+    pub fn iter_words(&self) -> TrieIterator<'_> {
         TrieIterator::new(&self.root)
     }
 
 
-    pub fn to_json(&self) -> anyhow::Result<String> {
-        serde_json::to_string(self)?
-            .parse()
-            .map_err(|e| anyhow::anyhow!("Failed to parse json: {}", e))
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string(self).
+            map_err(|e| Error::CorruptDictionary(format!("failed to serialize trie: {}", e)))
     }
 
-    pub fn from_json_file(file_path: &str) -> anyhow::Result<Self> {
+    pub fn from_json_file(file_path: &str) -> Result<Self> {
         let f = std::fs::File::open(file_path)?;
         let reader = BufReader::new(f);
         let mut decoder = GzDecoder::new(reader);
         let mut buffer = Vec::new();
-        decoder.read_to_end(&mut buffer)?;
-        Ok(serde_json::from_slice(&buffer)?)
+        // the file opened fine, so a failure here means it is not valid gzip
+        decoder.read_to_end(&mut buffer).
+            map_err(|e| Error::CorruptDictionary(format!("{}: {}", file_path, e)))?;
+        serde_json::from_slice(&buffer).
+            map_err(|e| Error::CorruptDictionary(format!("{}: {}", file_path, e)))
     }
 
-    pub fn to_json_file(&self, file_path: &str) -> anyhow::Result<()> {
+    pub fn to_json_file(&self, file_path: &str) -> Result<()> {
         let json = self.to_json()?;
         let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::best());
         encoder.write_all(json.as_bytes())?;
@@ -89,33 +143,31 @@ impl Trie {
     }
 }
 
-pub struct TrieIterator {
-    stack: Vec<(String, TrieNode)>,
+pub struct TrieIterator<'a> {
+    stack: Vec<(String, &'a TrieNode)>,
 }
 
-impl TrieIterator {
-    fn new(node: &TrieNode) -> Self {
-        let mut stack = Vec::new();
-        stack.push((String::new(), node.clone()));
+impl<'a> TrieIterator<'a> {
+    fn new(node: &'a TrieNode) -> Self {
+        let stack = vec![(String::new(), node)];
         TrieIterator { stack }
     }
 }
 
-impl Iterator for TrieIterator {
+impl Iterator for TrieIterator<'_> {
     type Item = String;
 
     fn next(&mut self) -> Option<Self::Item> {
-        while let Some((mut prefix, mut node)) = self.stack.pop() {
-            for (ch, child) in node.children.iter_mut() {
+        while let Some((prefix, node)) = self.stack.pop() {
+            // pushed last to first, so the first child is visited next
+            for (ch, child) in node.children.iter().rev() {
                 let mut new_prefix = prefix.clone();
                 new_prefix.push(*ch);
-                self.stack.push((new_prefix, child.clone()));
+                self.stack.push((new_prefix, child));
             }
 
             if node.is_end_of_word {
-                let word = prefix.clone();
-                prefix.pop();
-                return Some(word);
+                return Some(prefix);
             }
         }
         None
@@ -135,12 +187,40 @@ mod test {
         trie.insert("hey");
         trie.insert("world");
 
-        assert_eq!(trie.contains("hello"), true);
-        assert_eq!(trie.contains("Hello"), true);
-        assert_eq!(trie.contains("hello world"), false);
+        assert!(trie.contains("hello"));
+        assert!(trie.contains("Hello"));
+        assert!(!trie.contains("hello world"));
 
         trie.insert("hello world");
-        assert_eq!(trie.contains("hello world"), true);
+        assert!(trie.contains("hello world"));
+    }
+
+    #[test]
+    fn test_prefix_lengths() {
+        let mut trie = Trie::new();
+        trie.insert("he");
+        trie.insert("hell");
+        trie.insert("hello");
+        assert_eq!(vec![2, 4, 5], trie.prefix_lengths("Helloworld".chars()));
+        assert_eq!(Vec::<usize>::new(), trie.prefix_lengths("world".chars()));
+        let chars: Vec<char> = "oh, hello".chars().collect();
+        assert_eq!(vec![2, 4, 5], trie.prefix_lengths(chars[4..].iter().copied()));
+    }
+
+    #[test]
+    fn test_reversed() {
+        let mut trie = Trie::new();
+        trie.insert("hello");
+        trie.insert("help");
+        assert!(trie.contains_prefix("HEL".chars()));
+        assert!(trie.contains_prefix("help".chars()));
+        assert!(!trie.contains_prefix("helpful".chars()));
+
+        let reversed = trie.reversed();
+        assert!(reversed.contains("olleh"));
+        assert!(reversed.contains_prefix("pl".chars()));
+        assert!(reversed.contains_prefix("help".chars().rev().take(2)));
+        assert!(!reversed.contains("hello"));
     }
 
     #[test]
@@ -155,7 +235,9 @@ mod test {
         for word in trie.iter_words() {
             words_got.push(word);
         }
-        words_got.sort();
+        // listed in alphabetical order
+        assert_eq!(words_got, trie.iter_words().collect::<Vec<_>>());
+        assert!(words_got.is_sorted());
         assert_eq!(vec![
             "hello".to_string(), "hey".to_string(),
             "hi".to_string(), "world".to_string()], words_got);
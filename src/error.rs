@@ -0,0 +1,138 @@
+use std::fmt;
+use std::io;
+use std::string::FromUtf8Error;
+
+use crate::alphabet::ForeignCharError;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Every error the library can return, so callers can tell a missing
+/// dictionary from a bad key or a message that could not be decoded.
+#[derive(Debug)]
+pub enum Error {
+    /// Reading or writing a file (dictionary, pad, pad state) failed.
+    Io(io::Error),
+    /// The dictionary file exists but is not a valid gzipped trie.
+    CorruptDictionary(String),
+    /// The key cannot be used with the cipher.
+    InvalidKey(String),
+    /// An argument other than the key is out of range.
+    InvalidArgument(String),
+    /// The symbols do not form a valid alphabet.
+    InvalidAlphabet(String),
+    /// A letter is outside of the alphabet and the policy rejects it.
+    ForeignCharacter(ForeignCharError),
+    /// The one time pad is shorter than the message.
+    KeyTooShort { key_len: usize, message_len: usize },
+    /// Ciphertext is not valid base64.
+    InvalidBase64(base64::DecodeError),
+    /// Decrypted bytes are not valid UTF-8 text.
+    InvalidUtf8(FromUtf8Error),
+    /// The ciphertext or the pad book state is malformed.
+    InvalidPad(String),
+    /// No unused run of pad bytes is long enough for the message.
+    PadExhausted { requested: u64, remaining: u64 },
+    /// No cipher is registered under the name.
+    UnknownCipher { name: String, known: Vec<String> },
+    /// Every candidate key was tried and none produced a plausible plaintext.
+    ExhaustedSearch,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "i/o error: {}", e),
+            Error::CorruptDictionary(reason) => write!(f, "corrupt dictionary: {}", reason),
+            Error::InvalidKey(reason) => write!(f, "invalid key: {}", reason),
+            Error::InvalidArgument(reason) => write!(f, "invalid argument: {}", reason),
+            Error::InvalidAlphabet(reason) => write!(f, "invalid alphabet: {}", reason),
+            Error::ForeignCharacter(e) => write!(f, "{}", e),
+            Error::KeyTooShort { key_len, message_len } =>
+                write!(f, "one time pad key is {} bytes but the message is {} bytes", key_len, message_len),
+            Error::InvalidBase64(e) => write!(f, "invalid base64: {}", e),
+            Error::InvalidUtf8(e) => write!(f, "invalid utf-8: {}", e),
+            Error::InvalidPad(reason) => write!(f, "invalid pad: {}", reason),
+            Error::PadExhausted { requested, remaining } =>
+                write!(f, "pad exhausted: no unused run of {} bytes left ({} bytes remaining in total)", requested, remaining),
+            Error::UnknownCipher { name, known } =>
+                write!(f, "unknown cipher {:?}, expected one of: {}", name, known.join(", ")),
+            Error::ExhaustedSearch => write!(f, "message could not be decoded"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::ForeignCharacter(e) => Some(e),
+            Error::InvalidBase64(e) => Some(e),
+            Error::InvalidUtf8(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<ForeignCharError> for Error {
+    fn from(e: ForeignCharError) -> Self {
+        Error::ForeignCharacter(e)
+    }
+}
+
+impl From<base64::DecodeError> for Error {
+    fn from(e: base64::DecodeError) -> Self {
+        Error::InvalidBase64(e)
+    }
+}
+
+impl From<FromUtf8Error> for Error {
+    fn from(e: FromUtf8Error) -> Self {
+        Error::InvalidUtf8(e)
+    }
+}
+
+// the streaming adapters can only report `io::Error`, keep the original error as its payload
+impl From<Error> for io::Error {
+    fn from(e: Error) -> Self {
+        match e {
+            Error::Io(e) => e,
+            Error::ForeignCharacter(_) | Error::InvalidUtf8(_) => io::Error::new(io::ErrorKind::InvalidData, e),
+            _ => io::Error::new(io::ErrorKind::InvalidInput, e),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        assert_eq!("message could not be decoded", Error::ExhaustedSearch.to_string());
+        assert_eq!(
+            "unknown cipher \"enigma\", expected one of: atbash, caesar",
+            Error::UnknownCipher { name: "enigma".to_string(), known: vec!["atbash".to_string(), "caesar".to_string()] }.to_string()
+        );
+        let foreign = Error::from(ForeignCharError { ch: 'é', position: 3 });
+        assert_eq!("letter 'é' at position 3 is not part of the alphabet", foreign.to_string());
+        assert!(std::error::Error::source(&foreign).is_some());
+    }
+
+    #[test]
+    fn test_into_io_error() {
+        let err = io::Error::from(Error::ForeignCharacter(ForeignCharError { ch: 'é', position: 0 }));
+        assert_eq!(io::ErrorKind::InvalidData, err.kind());
+        let inner = err.into_inner().expect("payload").downcast::<Error>().expect("crate error");
+        assert!(matches!(*inner, Error::ForeignCharacter(_)));
+
+        let err = io::Error::from(Error::Io(io::Error::new(io::ErrorKind::NotFound, "missing")));
+        assert_eq!(io::ErrorKind::NotFound, err.kind());
+    }
+}
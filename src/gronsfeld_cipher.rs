@@ -0,0 +1,212 @@
+use std::io;
+
+use rand::{CryptoRng, Rng, RngCore};
+
+use crate::alphabet::{Alphabet, ASCII_LETTERS};
+use crate::candidate::{self, Candidate};
+use crate::cipher::Cipher;
+use crate::detection::frequency::LanguageProfile;
+use crate::detection::ngram::NgramScorer;
+use crate::error::{Error, Result};
+use crate::keygen::GenerateKey;
+use crate::stream::{CharTransform, StreamingCipher, TextTransform};
+use crate::vigenere_cipher::{refine, shortest_period, solve_columns, unshift};
+
+pub fn encode(message: &str, key: &str) -> Result<String> {
+    handle(message, &shifts(key)?, &ASCII_LETTERS, true)
+}
+
+
+pub fn decode(message: &str, key: &str) -> Result<String> {
+    handle(message, &shifts(key)?, &ASCII_LETTERS, false)
+}
+
+
+pub fn encode_with(message: &str, key: &str, alphabet: &Alphabet) -> Result<String> {
+    handle(message, &shifts(key)?, alphabet, true)
+}
+
+
+pub fn decode_with(message: &str, key: &str, alphabet: &Alphabet) -> Result<String> {
+    handle(message, &shifts(key)?, alphabet, false)
+}
+
+
+/// Recovers the key of an English message for every key length up to
+/// `max_key_length`: each column is solved as a Caesar shift of 0 to 9 by
+/// letter frequencies, then the digits are refined with quadgram fitness.
+/// Keys that repeat a shorter one are left out. Ranked by fitness, best first;
+/// needs a couple of hundred letters to be reliable.
+pub fn crack(message: &str, max_key_length: usize) -> Result<Vec<Candidate<String>>> {
+    let letters = ASCII_LETTERS.indices(message);
+    if letters.is_empty() || max_key_length == 0 {
+        return Err(Error::InvalidArgument("need letters to analyse and a key length of at least 1".to_string()));
+    }
+    let profile = LanguageProfile::english();
+    let scorer = NgramScorer::english(letters.len().min(4))?;
+    let mut candidates = Vec::new();
+    for length in 1..=max_key_length.min(letters.len()) {
+        let mut key = solve_columns(&letters, length, &profile, 10, unshift);
+        let score = refine(&letters, &mut key, scorer, 10, unshift);
+        if shortest_period(&key).len() < length {
+            continue;
+        }
+        let key: String = key.iter().map(|&digit| char::from(b'0' + digit as u8)).collect();
+        let decoded = decode(message, &key)?;
+        candidates.push(Candidate::new(key, decoded, score));
+    }
+    candidate::rank(&mut candidates);
+    Ok(candidates)
+}
+
+fn shifts(key: &str) -> Result<Vec<usize>> {
+    let digits: Option<Vec<usize>> = key.chars().
+        filter(|ch| !ch.is_whitespace()).
+        map(|ch| ch.to_digit(10).map(|digit| digit as usize)).
+        collect();
+    match digits {
+        Some(digits) if !digits.is_empty() => Ok(digits),
+        _ => Err(Error::InvalidKey(format!("gronsfeld key must be a number, got {:?}", key))),
+    }
+}
+
+/// Gronsfeld cipher: Vigenere with a numeric key, each digit shifting one
+/// letter. Characters outside the alphabet pass through and do not use up
+/// key digits.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gronsfeld {
+    key: String,
+    alphabet: Alphabet,
+}
+
+impl Gronsfeld {
+    pub fn new(key: &str) -> Result<Self> {
+        Self::with_alphabet(key, ASCII_LETTERS.clone())
+    }
+
+    pub fn with_alphabet(key: &str, alphabet: Alphabet) -> Result<Self> {
+        let key = key.to_string();
+        Self::validate_key(&key)?;
+        Ok(Self { key, alphabet })
+    }
+}
+
+impl Cipher for Gronsfeld {
+    type Key = String;
+
+    fn validate_key(key: &String) -> Result<()> {
+        shifts(key).map(|_| ())
+    }
+
+    fn key(&self) -> &String {
+        &self.key
+    }
+
+    fn encrypt(&self, message: &str) -> Result<String> {
+        encode_with(message, &self.key, &self.alphabet)
+    }
+
+    fn decrypt(&self, message: &str) -> Result<String> {
+        decode_with(message, &self.key, &self.alphabet)
+    }
+}
+
+impl StreamingCipher for Gronsfeld {
+    type Encryptor = TextTransform<GronsfeldStream>;
+    type Decryptor = TextTransform<GronsfeldStream>;
+
+    fn encryptor(&self) -> Self::Encryptor {
+        TextTransform::new(GronsfeldStream::new(shifts(&self.key).expect("validated key"), self.alphabet.clone(), true))
+    }
+
+    fn decryptor(&self) -> Self::Decryptor {
+        TextTransform::new(GronsfeldStream::new(shifts(&self.key).expect("validated key"), self.alphabet.clone(), false))
+    }
+}
+
+/// Character by character form of the Gronsfeld cipher that remembers its
+/// position in the key, used by the streaming adapters.
+pub struct GronsfeldStream {
+    shifts: Vec<usize>,
+    next_shift: usize,
+    alphabet: Alphabet,
+    encode: bool,
+}
+
+impl GronsfeldStream {
+    fn new(shifts: Vec<usize>, alphabet: Alphabet, encode: bool) -> Self {
+        Self { shifts, next_shift: 0, alphabet, encode }
+    }
+}
+
+impl CharTransform for GronsfeldStream {
+    fn transform(&mut self, ch: char, output: &mut String) -> io::Result<()> {
+        if !self.alphabet.contains(ch) {
+            output.push(ch);
+            return Ok(());
+        }
+        let shift = self.shifts[self.next_shift % self.shifts.len()];
+        output.push(self.alphabet.shift(ch, shift, self.encode).unwrap_or(ch));
+        self.next_shift += 1;
+        Ok(())
+    }
+}
+
+impl GenerateKey for Gronsfeld {
+    fn generate_key<R: RngCore + CryptoRng + ?Sized>(rng: &mut R, length: usize) -> String {
+        (0..length.max(1)).map(|_| rng.random_range('0'..='9')).collect()
+    }
+}
+
+fn handle(message: &str, shifts: &[usize], alphabet: &Alphabet, encode: bool) -> Result<String> {
+    let mut shifts = shifts.iter().cycle();
+    Ok(message.chars().
+        map(|ch| match alphabet.contains(ch) {
+            true => alphabet.shift(ch, *shifts.next().expect("key is not empty"), encode).unwrap_or(ch),
+            false => ch,
+        }).
+        collect())
+}
+
+
+#[cfg(test)]
+mod test {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+
+    const MESSAGE: &str = "On offering to help the blind man, the man who then stole his car, had not, at that precise moment,
+  had any evil intention, quite the contrary, what he did was nothing more than obey those feelings of generosity and
+  altruism which, as everyone knows, are the two best traits of human nature.";
+
+    #[test]
+    fn test_encode() {
+        assert_eq!("Duxbhn, Dlpxho!", encode("Attack, Chosen!", "31415").expect("digits"));
+        assert_eq!("Attack, Chosen!", decode("Duxbhn, Dlpxho!", "31415").expect("digits"));
+        assert_eq!(crate::vigenere_cipher::encode(MESSAGE, "dbebf"), encode(MESSAGE, "3 1 4 1 5").expect("digits"));
+        assert!(matches!(encode("text", "pi"), Err(Error::InvalidKey(_))));
+        assert!(matches!(encode("text", ""), Err(Error::InvalidKey(_))));
+    }
+
+    #[test]
+    fn test_crack() {
+        for key in ["31415", "7", "2718281"] {
+            let candidates = crack(&encode(MESSAGE, key).expect("digits"), 10).expect("has letters");
+            assert_eq!(key, candidates[0].key);
+            assert_eq!(MESSAGE, candidates[0].decoded_message);
+        }
+        assert!(matches!(crack("1984", 10), Err(Error::InvalidArgument(_))));
+    }
+
+    #[test]
+    fn test_cipher_trait() {
+        let mut rng = StdRng::seed_from_u64(24);
+        let key = Gronsfeld::generate_key(&mut rng, 8);
+        assert!(key.chars().all(|ch| ch.is_ascii_digit()));
+        let cipher = Gronsfeld::with_alphabet(&key, Alphabet::cyrillic()).expect("generated keys are valid");
+        assert_eq!(&key, cipher.key());
+        let message = "Съешь же ещё этих мягких французских булок";
+        assert_eq!(message, cipher.decrypt(&cipher.encrypt(message).expect("encrypt")).expect("decrypt"));
+    }
+}
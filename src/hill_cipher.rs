@@ -0,0 +1,408 @@
+use std::fmt;
+
+use rand::{CryptoRng, Rng, RngCore};
+
+use crate::affine_cipher::modular_inverse;
+use crate::alphabet::{Alphabet, ASCII_LETTERS};
+use crate::cipher::Cipher;
+use crate::error::{Error, Result};
+use crate::keygen::GenerateKey;
+
+/// Square key matrix of the Hill cipher, entries taken modulo the alphabet
+/// length. A block of letters `p` (a column vector) encrypts to `K·p`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Matrix {
+    size: usize,
+    // row by row
+    entries: Vec<usize>,
+}
+
+impl Matrix {
+    pub fn new(rows: &[Vec<usize>]) -> Result<Self> {
+        let size = rows.len();
+        if size == 0 || rows.iter().any(|row| row.len() != size) {
+            return Err(Error::InvalidKey("hill key must be a non-empty square matrix".to_string()));
+        }
+        Ok(Self { size, entries: rows.concat() })
+    }
+
+    /// Matrix filled row by row from `entries`, which must be a square number
+    /// of values.
+    pub fn from_entries(entries: &[usize]) -> Result<Self> {
+        let size = (1..=entries.len()).find(|size| size * size >= entries.len()).unwrap_or(0);
+        if size == 0 || size * size != entries.len() {
+            return Err(Error::InvalidKey(format!(
+                "hill key must have a square number of entries (4, 9, 16, ...), got {}", entries.len()
+            )));
+        }
+        Ok(Self { size, entries: entries.to_vec() })
+    }
+
+    /// Matrix filled row by row with the positions of the letters of
+    /// `keyword` in `alphabet` (`"hill"` is 2×2, `"gybnqkurp"` 3×3).
+    pub fn from_keyword(keyword: &str, alphabet: &Alphabet) -> Result<Self> {
+        Self::from_entries(&alphabet.indices(keyword))
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn get(&self, row: usize, column: usize) -> usize {
+        self.entries[row * self.size + column]
+    }
+
+    pub fn rows(&self) -> Vec<Vec<usize>> {
+        self.entries.chunks(self.size).map(|row| row.to_vec()).collect()
+    }
+
+    pub fn determinant(&self, modulus: usize) -> usize {
+        let mut rows = self.signed_rows(modulus);
+        let sign = triangularize(&mut rows, self.size, modulus as i64);
+        let product = (0..self.size).fold(sign, |product, i| product * rows[i][i] % modulus as i64);
+        product.rem_euclid(modulus as i64) as usize
+    }
+
+    /// The inverse modulo `modulus`, which exists exactly when the
+    /// determinant is coprime to it.
+    pub fn inverse(&self, modulus: usize) -> Result<Matrix> {
+        let determinant = self.determinant(modulus);
+        if modular_inverse(determinant, modulus).is_none() {
+            return Err(Error::InvalidKey(format!(
+                "hill key is not invertible mod {}: its determinant {} shares a factor with {}", modulus, determinant, modulus
+            )));
+        }
+        let mut rows = self.signed_rows(modulus);
+        for (i, row) in rows.iter_mut().enumerate() {
+            row.extend((0..self.size).map(|j| (i == j) as i64));
+        }
+        solve(&mut rows, self.size, modulus as i64).expect("determinant is invertible");
+        let entries = rows.iter().take(self.size).
+            flat_map(|row| row[self.size..].iter().map(|&x| x as usize)).
+            collect();
+        Ok(Matrix { size: self.size, entries })
+    }
+
+    // K·block for every block of `letters`
+    fn apply(&self, letters: &[usize], modulus: usize) -> Vec<usize> {
+        letters.chunks_exact(self.size).
+            flat_map(|block| (0..self.size).map(move |row| {
+                (0..self.size).map(|column| self.get(row, column) * block[column]).sum::<usize>() % modulus
+            })).
+            collect()
+    }
+
+    fn reduced(&self, modulus: usize) -> Matrix {
+        Matrix { size: self.size, entries: self.entries.iter().map(|x| x % modulus).collect() }
+    }
+
+    fn signed_rows(&self, modulus: usize) -> Vec<Vec<i64>> {
+        self.entries.chunks(self.size).
+            map(|row| row.iter().map(|&x| (x % modulus) as i64).collect()).
+            collect()
+    }
+}
+
+impl fmt::Display for Matrix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, row) in self.rows().iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            let row: Vec<String> = row.iter().map(|x| x.to_string()).collect();
+            write!(f, "{}", row.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+// Row reduction over the integers mod `modulus`, which need not be prime: a
+// column is cleared with Euclid's algorithm on pairs of rows, so only
+// invertible row operations are used. Brings the first `columns` columns to
+// upper triangular form and returns the sign the row swaps gave the determinant.
+fn triangularize(rows: &mut [Vec<i64>], columns: usize, modulus: i64) -> i64 {
+    let mut sign = 1;
+    for column in 0..columns.min(rows.len()) {
+        for row in column + 1..rows.len() {
+            while rows[row][column] != 0 {
+                let quotient = rows[column][column] / rows[row][column];
+                for k in 0..rows[row].len() {
+                    rows[column][k] = (rows[column][k] - quotient * rows[row][k]).rem_euclid(modulus);
+                }
+                rows.swap(column, row);
+                sign = -sign;
+            }
+        }
+    }
+    sign
+}
+
+// Reduces the first `columns` columns to the identity in the top rows, which
+// leaves the solution of the system in the remaining columns. `None` if a
+// pivot is not invertible, i.e. the system has no unique solution.
+fn solve(rows: &mut [Vec<i64>], columns: usize, modulus: i64) -> Option<()> {
+    if rows.len() < columns {
+        return None;
+    }
+    triangularize(rows, columns, modulus);
+    for column in (0..columns).rev() {
+        let pivot = modular_inverse(rows[column][column] as usize, modulus as usize)? as i64;
+        for x in rows[column].iter_mut() {
+            *x = *x * pivot % modulus;
+        }
+        for row in 0..column {
+            let factor = rows[row][column];
+            for k in 0..rows[row].len() {
+                rows[row][k] = (rows[row][k] - factor * rows[column][k]).rem_euclid(modulus);
+            }
+        }
+    }
+    Some(())
+}
+
+// the key reduced modulo the alphabet length, so `K·p` cannot overflow, and its inverse
+fn check_key(key: &Matrix, alphabet: &Alphabet) -> Result<(Matrix, Matrix)> {
+    let key = key.reduced(alphabet.len());
+    let inverse = key.inverse(alphabet.len())?;
+    Ok((key, inverse))
+}
+
+pub fn encode(message: &str, key: &Matrix) -> Result<String> {
+    Hill::new(key.clone())?.encrypt(message)
+}
+
+
+pub fn decode(message: &str, key: &Matrix) -> Result<String> {
+    Hill::new(key.clone())?.decrypt(message)
+}
+
+
+/// Recovers the key from a plaintext and its ciphertext over the ASCII
+/// letters, see [`known_plaintext_attack_with`].
+pub fn known_plaintext_attack(plaintext: &str, ciphertext: &str, size: usize) -> Result<Matrix> {
+    known_plaintext_attack_with(plaintext, ciphertext, size, &ASCII_LETTERS)
+}
+
+
+/// Solves `C = K·P` for the `size`×`size` key, where the columns of `P` and
+/// `C` are the aligned blocks of plaintext and ciphertext letters (anything
+/// outside of `alphabet` is skipped on both sides). Needs at least `size`
+/// blocks, and enough of them independent mod the alphabet length.
+pub fn known_plaintext_attack_with(plaintext: &str, ciphertext: &str, size: usize, alphabet: &Alphabet) -> Result<Matrix> {
+    if size == 0 {
+        return Err(Error::InvalidArgument("hill key size must be at least 1".to_string()));
+    }
+    let modulus = alphabet.len();
+    let plain = alphabet.indices(plaintext);
+    let cipher = alphabet.indices(ciphertext);
+    // each block gives one equation pᵀ·Kᵀ = cᵀ, a row of [P | C] in the transposed system
+    let mut rows: Vec<Vec<i64>> = plain.chunks_exact(size).zip(cipher.chunks_exact(size)).
+        map(|(p, c)| p.iter().chain(c).map(|&x| x as i64).collect()).
+        collect();
+    solve(&mut rows, size, modulus as i64).ok_or_else(|| Error::InvalidArgument(format!(
+        "the {} aligned blocks of plaintext do not determine a {}x{} key mod {}", rows.len(), size, size, modulus
+    )))?;
+    let entries = (0..size).
+        flat_map(|row| (0..size).map(move |column| (row, column))).
+        map(|(row, column)| rows[column][size + row] as usize).
+        collect();
+    let key = Matrix { size, entries };
+    // the key comes from the first independent blocks, every other block must agree with it
+    let aligned = plain.len().min(cipher.len()) / size * size;
+    if key.apply(&plain[..aligned], modulus) != cipher[..aligned] {
+        return Err(Error::InvalidArgument("the plaintext and ciphertext blocks do not share one hill key".to_string()));
+    }
+    Ok(key)
+}
+
+/// Hill cipher: blocks of letters are multiplied by the key matrix. Other
+/// characters stay in place and the case of every letter is kept. The last
+/// block is padded with `x` (configurable) if needed, and the padding is left
+/// in the decrypted text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hill {
+    key: Matrix,
+    inverse: Matrix,
+    alphabet: Alphabet,
+    padding: char,
+}
+
+impl Hill {
+    pub fn new(key: Matrix) -> Result<Self> {
+        Self::with_alphabet(key, ASCII_LETTERS.clone())
+    }
+
+    /// The key is stored reduced modulo the alphabet length.
+    pub fn with_alphabet(key: Matrix, alphabet: Alphabet) -> Result<Self> {
+        let (key, inverse) = check_key(&key, &alphabet)?;
+        let padding = if alphabet.contains('x') { 'x' } else { alphabet.symbols()[alphabet.len() - 1] };
+        Ok(Self { key, inverse, alphabet, padding })
+    }
+
+    pub fn with_padding(mut self, padding: char) -> Result<Self> {
+        if !self.alphabet.contains(padding) {
+            return Err(Error::InvalidArgument(format!("padding {:?} is not part of the alphabet", padding)));
+        }
+        self.padding = padding;
+        Ok(self)
+    }
+
+    // applies `matrix` to the letters of `message`, writing them back in their places
+    fn handle(&self, message: &str, matrix: &Matrix) -> String {
+        let mut chars: Vec<char> = message.chars().collect();
+        let mut letters = self.alphabet.indices(message);
+        while !letters.len().is_multiple_of(matrix.size) {
+            letters.push(self.alphabet.index_of(self.padding).expect("padding is in the alphabet"));
+            chars.push(self.padding);
+        }
+        let mut transformed = matrix.apply(&letters, self.alphabet.len()).into_iter();
+        chars.into_iter().
+            map(|ch| match self.alphabet.contains(ch) {
+                true => self.alphabet.symbol_like(transformed.next().expect("one per letter"), ch),
+                false => ch,
+            }).
+            collect()
+    }
+}
+
+impl Cipher for Hill {
+    type Key = Matrix;
+
+    // the same check `Hill::new` runs; `with_alphabet` runs it for its own alphabet
+    fn validate_key(key: &Matrix) -> Result<()> {
+        check_key(key, &ASCII_LETTERS).map(|_| ())
+    }
+
+    fn key(&self) -> &Matrix {
+        &self.key
+    }
+
+    fn encrypt(&self, message: &str) -> Result<String> {
+        Ok(self.handle(message, &self.key))
+    }
+
+    fn decrypt(&self, message: &str) -> Result<String> {
+        let letters = self.alphabet.indices(message).len();
+        if !letters.is_multiple_of(self.key.size) {
+            return Err(Error::InvalidArgument(format!(
+                "hill ciphertext must have a multiple of {} letters, got {}", self.key.size, letters
+            )));
+        }
+        Ok(self.handle(message, &self.inverse))
+    }
+}
+
+impl GenerateKey for Hill {
+    // `length` is the matrix size, at least 2; draws until the matrix is invertible mod 26
+    fn generate_key<R: RngCore + CryptoRng + ?Sized>(rng: &mut R, length: usize) -> Matrix {
+        let size = length.max(2);
+        loop {
+            let entries: Vec<usize> = (0..size * size).map(|_| rng.random_range(0..ASCII_LETTERS.len())).collect();
+            let key = Matrix::from_entries(&entries).expect("square number of entries");
+            if key.inverse(ASCII_LETTERS.len()).is_ok() {
+                return key;
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+
+    fn matrix(rows: &[&[usize]]) -> Matrix {
+        Matrix::new(&rows.iter().map(|row| row.to_vec()).collect::<Vec<_>>()).expect("square")
+    }
+
+    #[test]
+    fn test_matrix() {
+        let key = matrix(&[&[6, 24, 1], &[13, 16, 10], &[20, 17, 15]]);
+        assert_eq!(key, Matrix::from_keyword("GYBNQKURP", &ASCII_LETTERS).expect("9 letters"));
+        assert_eq!(25, key.determinant(26));
+        let inverse = key.inverse(26).expect("invertible");
+        assert_eq!(vec![vec![8, 5, 10], vec![21, 8, 21], vec![21, 12, 8]], inverse.rows());
+        assert_eq!("8 5 10\n21 8 21\n21 12 8", inverse.to_string());
+
+        let err = matrix(&[&[2, 4], &[1, 3]]).inverse(26).expect_err("determinant 2");
+        assert_eq!("invalid key: hill key is not invertible mod 26: its determinant 2 shares a factor with 26", err.to_string());
+        // no entry of the first column is invertible mod 26, but the matrix is
+        assert!(matrix(&[&[2, 1], &[13, 1]]).inverse(26).is_ok());
+
+        assert!(Matrix::new(&[vec![1, 2], vec![3]]).is_err());
+        assert!(Matrix::from_keyword("hills", &ASCII_LETTERS).is_err());
+        assert_eq!(matrix(&[&[3, 3], &[2, 5]]), Matrix::from_entries(&[3, 3, 2, 5]).expect("4 entries"));
+    }
+
+    #[test]
+    fn test_encode() {
+        let key = Matrix::from_keyword("GYBNQKURP", &ASCII_LETTERS).expect("9 letters");
+        assert_eq!("POH", encode("ACT", &key).expect("valid key"));
+        assert_eq!("ACT", decode("POH", &key).expect("valid key"));
+
+        let key = matrix(&[&[3, 3], &[2, 5]]);
+        let encoded = encode("Help me, now!", &key).expect("valid key");
+        assert_eq!("Hiat ws, dsf!d", encoded);
+        assert_eq!("Help me, now!x", decode(&encoded, &key).expect("valid key"));
+        assert!(matches!(decode("abc", &key), Err(Error::InvalidArgument(_))));
+        assert!(matches!(encode("text", &matrix(&[&[2, 4], &[1, 3]])), Err(Error::InvalidKey(_))));
+
+        // huge entries are reduced instead of overflowing
+        let huge = Hill::new(matrix(&[&[usize::MAX, 0], &[26, 1]])).expect("determinant 15 mod 26");
+        assert_eq!(&matrix(&[&[15, 0], &[0, 1]]), huge.key());
+        assert_eq!("Bejp", huge.encrypt("Help").expect("encrypt"));
+        assert_eq!("Help", huge.decrypt("Bejp").expect("decrypt"));
+
+        let cipher = Hill::new(key).expect("valid key").with_padding('q').expect("a letter");
+        assert_eq!("oddq", cipher.decrypt(&cipher.encrypt("odd").expect("encrypt")).expect("decrypt"));
+    }
+
+    #[test]
+    fn test_alphabets() {
+        let cyrillic = Alphabet::cyrillic();
+        let key = matrix(&[&[2, 3], &[1, 4]]);
+        // invertible mod 26, which is what validate_key and Hill::new check, but not mod 33
+        assert!(Hill::validate_key(&matrix(&[&[3, 3], &[2, 5]])).is_ok());
+        assert!(matches!(Hill::with_alphabet(matrix(&[&[3, 3], &[2, 5]]), cyrillic.clone()), Err(Error::InvalidKey(_))));
+        let cipher = Hill::with_alphabet(key, cyrillic).expect("invertible mod 33");
+        let encoded = cipher.encrypt("Привет, мир").expect("encrypt");
+        assert_eq!("Привет, мир", cipher.decrypt(&encoded).expect("decrypt").trim_end_matches('я'));
+    }
+
+    #[test]
+    fn test_known_plaintext_attack() {
+        let key = Matrix::from_keyword("GYBNQKURP", &ASCII_LETTERS).expect("9 letters");
+        let plaintext = "Meet me at the usual place at ten rather than eight";
+        let ciphertext = encode(plaintext, &key).expect("valid key");
+        assert_eq!(key, known_plaintext_attack(plaintext, &ciphertext, 3).expect("enough blocks"));
+
+        let key = matrix(&[&[3, 3], &[2, 5]]);
+        assert_eq!(key, known_plaintext_attack("attack at dawn", &encode("attack at dawn", &key).expect("valid key"), 2).expect("enough blocks"));
+
+        assert!(matches!(known_plaintext_attack("ab", "xy", 2), Err(Error::InvalidArgument(_))));
+        // the same block twice says nothing new
+        assert!(matches!(known_plaintext_attack("abab", "cdcd", 2), Err(Error::InvalidArgument(_))));
+        // the first blocks determine a key, but a later block contradicts it
+        let mut ciphertext = encode("attack at dawn", &key).expect("valid key");
+        ciphertext.replace_range(ciphertext.len() - 1.., "a");
+        assert!(matches!(known_plaintext_attack("attack at dawn", &ciphertext, 2), Err(Error::InvalidArgument(_))));
+    }
+
+    #[test]
+    fn test_cipher_trait() {
+        let mut rng = StdRng::seed_from_u64(20);
+        for size in 2..=5 {
+            let key = Hill::generate_key(&mut rng, size);
+            assert_eq!(size, key.size());
+            let cipher = Hill::new(key.clone()).expect("generated keys are valid");
+            assert_eq!(&key, cipher.key());
+            let message = "Hill ciphers mix whole blocks";
+            let encrypted = cipher.encrypt(message).expect("encrypt");
+            assert!(cipher.decrypt(&encrypted).expect("decrypt").starts_with(message));
+            assert_eq!(key, known_plaintext_attack(&message.repeat(3), &cipher.encrypt(&message.repeat(3)).expect("encrypt"), size).expect("enough blocks"));
+        }
+    }
+}
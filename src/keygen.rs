@@ -0,0 +1,80 @@
+use rand::{CryptoRng, Rng, RngCore, TryRngCore};
+use rand::rand_core::UnwrapErr;
+use rand::rngs::OsRng;
+
+use crate::alphabet::Alphabet;
+use crate::cipher::Cipher;
+
+/// The operating system's CSPRNG, the default source of every random key.
+pub type OsCsprng = UnwrapErr<OsRng>;
+
+pub fn os_rng() -> OsCsprng {
+    OsRng.unwrap_err()
+}
+
+/// Random key generation shared by every cipher. The randomness always comes
+/// from the caller so tests can inject a seeded generator, use [`os_rng`] otherwise.
+pub trait GenerateKey: Cipher {
+    /// Draws a fresh key from `rng`. `length` is the key length for ciphers
+    /// whose keys have one (Vigenere, one time pad) and ignored by the rest.
+    fn generate_key<R: RngCore + CryptoRng + ?Sized>(rng: &mut R, length: usize) -> Self::Key;
+}
+
+/// `length` bytes drawn uniformly over all 256 values.
+pub fn random_bytes<R: RngCore + CryptoRng + ?Sized>(rng: &mut R, length: usize) -> Vec<u8> {
+    let mut bytes = vec![0u8; length];
+    rng.fill_bytes(&mut bytes);
+    bytes
+}
+
+/// `length` symbols drawn uniformly from `alphabet`.
+pub fn random_symbols<R: RngCore + CryptoRng + ?Sized>(rng: &mut R, alphabet: &Alphabet, length: usize) -> String {
+    (0..length).
+        map(|_| alphabet.symbols()[rng.random_range(0..alphabet.len())]).
+        collect()
+}
+
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    use super::*;
+    use crate::caeser_cipher::Caesar;
+    use crate::one_time_pad::OneTimePad;
+    use crate::vigenere_cipher::Vigenere;
+
+    #[test]
+    fn test_random_bytes() {
+        let bytes = random_bytes(&mut os_rng(), 8192);
+        let distinct: HashSet<u8> = bytes.iter().copied().collect();
+        // 8192 uniform draws miss any given byte value with probability ~1e-14
+        assert_eq!(256, distinct.len());
+
+        let mut a = StdRng::seed_from_u64(3);
+        let mut b = StdRng::seed_from_u64(3);
+        assert_eq!(random_bytes(&mut a, 32), random_bytes(&mut b, 32));
+    }
+
+    #[test]
+    fn test_generate_key() {
+        let mut rng = StdRng::seed_from_u64(5);
+        let shift = Caesar::generate_key(&mut rng, 0);
+        assert!((1..26).contains(&shift));
+
+        let key = Vigenere::generate_key(&mut rng, 12);
+        assert_eq!(12, key.len());
+        assert!(key.chars().all(|ch| ch.is_ascii_lowercase()));
+        assert!(Vigenere::new(&key).is_ok());
+
+        let pad = OneTimePad::generate_key(&mut rng, 100);
+        assert_eq!(100, pad.len());
+
+        let symbols = random_symbols(&mut rng, &Alphabet::cyrillic(), 10);
+        assert_eq!(10, symbols.chars().count());
+        assert!(symbols.chars().all(|ch| Alphabet::cyrillic().contains(ch)));
+    }
+}
@@ -1,12 +1,52 @@
 #[allow(dead_code)]
+pub mod error;
+#[allow(dead_code)]
+pub mod alphabet;
+#[allow(dead_code)]
+pub mod cipher;
+#[allow(dead_code)]
+pub mod candidate;
+#[allow(dead_code)]
 pub mod atbash_cipher;
 #[allow(dead_code)]
+pub mod affine_cipher;
+#[allow(dead_code)]
+pub mod autokey_cipher;
+#[allow(dead_code)]
+pub mod beaufort_cipher;
+#[allow(dead_code)]
 pub mod caeser_cipher;
 #[allow(dead_code)]
+pub mod columnar_transposition;
+#[allow(dead_code)]
 pub mod vigenere_cipher;
 #[allow(dead_code)]
+pub mod gronsfeld_cipher;
+#[allow(dead_code)]
+pub mod hill_cipher;
+#[allow(dead_code)]
 pub mod one_time_pad;
 #[allow(dead_code)]
+pub mod playfair_cipher;
+#[allow(dead_code)]
+pub mod porta_cipher;
+#[allow(dead_code)]
+pub mod rail_fence_cipher;
+#[allow(dead_code)]
 pub mod rot13;
 #[allow(dead_code)]
-pub mod detection;
\ No newline at end of file
+pub mod running_key_cipher;
+#[allow(dead_code)]
+pub mod substitution_cipher;
+#[allow(dead_code)]
+pub mod trithemius_cipher;
+#[allow(dead_code)]
+pub mod detection;
+#[allow(dead_code)]
+pub mod registry;
+#[allow(dead_code)]
+pub mod keygen;
+#[allow(dead_code)]
+pub mod pad_book;
+#[allow(dead_code)]
+pub mod stream;
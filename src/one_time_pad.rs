@@ -1,78 +1,191 @@
+use std::io;
+
 use base64::{alphabet, engine, Engine};
 use base64::engine::GeneralPurpose;
-use rand::{Rng, SeedableRng};
-use rand::rngs::StdRng;
+use rand::{CryptoRng, Rng, RngCore};
+
+use crate::cipher::Cipher;
+use crate::error::{Error, Result};
+use crate::keygen::{os_rng, GenerateKey, OsCsprng};
+use crate::stream::{StreamingCipher, Transform};
 
-struct KeyGenerator {
-    generated_key: String,
+/// Endless stream of pad bytes drawn uniformly over all 256 values, from the
+/// operating system's CSPRNG unless another generator is injected.
+pub struct KeyGenerator<R: RngCore + CryptoRng = OsCsprng> {
+    rng: R,
 }
 
 
 impl KeyGenerator {
-    fn new() -> Self {
-        KeyGenerator {
-            generated_key: String::new()
-        }
+    pub fn new() -> Self {
+        Self::with_rng(os_rng())
+    }
+}
+
+impl Default for KeyGenerator {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
-impl Iterator for KeyGenerator {
+impl<R: RngCore + CryptoRng> KeyGenerator<R> {
+    pub fn with_rng(rng: R) -> Self {
+        KeyGenerator { rng }
+    }
+}
+
+impl<R: RngCore + CryptoRng> Iterator for KeyGenerator<R> {
     type Item = u8;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let value: char = if cfg!(test) {
-            StdRng::seed_from_u64(self.generated_key.len() as u64).gen_range('A'..='z')
-        } else {
-            rand::thread_rng().gen_range('A'..='z')
-        };
-        self.generated_key.push(value);
-        Some(value as u8)
+        Some(self.rng.random())
     }
 }
 
+#[derive(Debug, Clone, PartialEq)]
 pub struct PadResponse {
-    pub key: String,
+    pub key: Vec<u8>,
     pub encoded_message: String,
 }
 
 
-fn encode(message: &str) -> PadResponse {
-    let mut key = KeyGenerator::new();
-    let mut encoded_message = String::new();
-    for ch in message.chars() {
-        let msg_ch = ch as u8;
-        let next_k = key.next().expect("excepted a randomly generated character");
-        let encoded_value = msg_ch ^ next_k;
-        encoded_message.push(encoded_value as char);
+/// XORs every byte of `message` with the matching byte of `key`. The key must
+/// be at least as long as the message; any extra key bytes are ignored.
+pub fn encrypt(message: &[u8], key: &[u8]) -> Result<Vec<u8>> {
+    if key.len() < message.len() {
+        return Err(Error::KeyTooShort { key_len: key.len(), message_len: message.len() });
     }
+    Ok(message.iter().zip(key).map(|(msg_b, key_b)| msg_b ^ key_b).collect())
+}
 
+
+/// Inverse of [`encrypt`], XOR is its own inverse so this only exists for readability.
+pub fn decrypt(ciphertext: &[u8], key: &[u8]) -> Result<Vec<u8>> {
+    encrypt(ciphertext, key)
+}
+
+
+fn engine() -> GeneralPurpose {
     let config = engine::GeneralPurposeConfig::new()
         .with_decode_padding_mode(engine::DecodePaddingMode::Indifferent);
-    let engine = GeneralPurpose::new(&alphabet::URL_SAFE, config);
+    GeneralPurpose::new(&alphabet::URL_SAFE, config)
+}
+
+
+/// Encrypts `message` under a fresh pad from the operating system's CSPRNG,
+/// returning the pad and the URL safe base64 ciphertext.
+pub fn encode(message: &str) -> PadResponse {
+    encode_with_rng(message, &mut os_rng())
+}
+
 
-    PadResponse {
-        key: key.generated_key,
-        encoded_message: engine.encode(encoded_message),
+pub fn encode_with_rng<R: RngCore + CryptoRng + ?Sized>(message: &str, rng: &mut R) -> PadResponse {
+    let key = OneTimePad::generate_key(rng, message.len());
+    let encoded_message = encode_with_key(message, &key).expect("generated key matches the message length");
+    PadResponse { key, encoded_message }
+}
+
+
+fn encode_with_key(message: &str, key: &[u8]) -> Result<String> {
+    Ok(engine().encode(encrypt(message.as_bytes(), key)?))
+}
+
+
+pub fn decode(message: &str, key: &[u8]) -> Result<String> {
+    let encoded_message = engine().decode(message)?;
+    Ok(String::from_utf8(decrypt(&encoded_message, key)?)?)
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OneTimePad {
+    key: Vec<u8>,
+}
+
+impl OneTimePad {
+    pub fn new(key: &[u8]) -> Result<Self> {
+        let key = key.to_vec();
+        Self::validate_key(&key)?;
+        Ok(Self { key })
+    }
+
+    /// Pad of `length` bytes from the operating system's CSPRNG.
+    pub fn generate(length: usize) -> Self {
+        let key = Self::generate_key(&mut os_rng(), length);
+        Self { key }
     }
 }
 
+// streams are raw bytes, unlike `Cipher::encrypt` there is no base64 encoding
+impl StreamingCipher for OneTimePad {
+    type Encryptor = PadStream;
+    type Decryptor = PadStream;
 
-fn decode(message: &str, key: &str) -> anyhow::Result<String> {
-    let config = engine::GeneralPurposeConfig::new()
-        .with_decode_padding_mode(engine::DecodePaddingMode::Indifferent);
-    let engine = GeneralPurpose::new(&alphabet::URL_SAFE, config);
-    let encoded_message = engine.decode(message)?;
+    fn encryptor(&self) -> PadStream {
+        PadStream { key: self.key.clone(), position: 0 }
+    }
+
+    fn decryptor(&self) -> PadStream {
+        self.encryptor()
+    }
+}
+
+/// XORs a byte stream with the pad, failing once the pad runs out.
+pub struct PadStream {
+    key: Vec<u8>,
+    position: usize,
+}
+
+impl Transform for PadStream {
+    // the input is used up to the end of the pad, past it is an error
+    fn update(&mut self, input: &[u8], output: &mut Vec<u8>) -> io::Result<usize> {
+        if !input.is_empty() && self.position == self.key.len() {
+            return Err(Error::KeyTooShort { key_len: self.key.len(), message_len: self.position + input.len() }.into());
+        }
+        let end = self.key.len().min(self.position + input.len());
+        let used = end - self.position;
+        output.extend(encrypt(&input[..used], &self.key[self.position..end]).expect("as long as the key"));
+        self.position = end;
+        Ok(used)
+    }
+}
+
+impl GenerateKey for OneTimePad {
+    fn generate_key<R: RngCore + CryptoRng + ?Sized>(rng: &mut R, length: usize) -> Vec<u8> {
+        crate::keygen::random_bytes(rng, length)
+    }
+}
 
-    Ok(encoded_message.
-        iter().
-        zip(key.chars()).map(|(msg_ch, next_k)|
-        (msg_ch ^ (next_k as u8)) as char
-    ).collect())
+impl Cipher for OneTimePad {
+    type Key = Vec<u8>;
+
+    fn validate_key(key: &Vec<u8>) -> Result<()> {
+        if key.is_empty() {
+            return Err(Error::InvalidKey("one time pad key must not be empty".to_string()));
+        }
+        Ok(())
+    }
+
+    fn key(&self) -> &Vec<u8> {
+        &self.key
+    }
+
+    // the ciphertext is URL safe base64 so it can travel as text
+    fn encrypt(&self, message: &str) -> Result<String> {
+        encode_with_key(message, &self.key)
+    }
+
+    fn decrypt(&self, message: &str) -> Result<String> {
+        decode(message, &self.key)
+    }
 }
 
 
 #[cfg(test)]
 mod test {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
     use super::*;
 
     const PLAIN_TEXT: &str = "On offering to help the blind man, the man who then stole his car, had not, at that precise moment,
@@ -86,15 +199,69 @@ mod test {
 
     #[test]
     fn test_encode() {
+        let pad = encode_with_rng(PLAIN_TEXT, &mut StdRng::seed_from_u64(1));
+        assert_eq!(pad, encode_with_rng(PLAIN_TEXT, &mut StdRng::seed_from_u64(1)));
+        assert_eq!(PLAIN_TEXT.len(), pad.key.len());
+        assert_eq!(PLAIN_TEXT, decode(&pad.encoded_message, &pad.key).expect("no errors"));
+
         let pad = encode(PLAIN_TEXT);
-        assert_eq!(EXPECTED_ENCODED, pad.encoded_message);
+        assert_ne!(pad.key, encode(PLAIN_TEXT).key);
+        assert_eq!(PLAIN_TEXT, decode(&pad.encoded_message, &pad.key).expect("no errors"));
+    }
+
+    #[test]
+    fn test_key_generator() {
+        let key: Vec<u8> = KeyGenerator::with_rng(StdRng::seed_from_u64(9)).take(4096).collect();
+        assert_eq!(key, KeyGenerator::with_rng(StdRng::seed_from_u64(9)).take(4096).collect::<Vec<u8>>());
+        // the old generator only produced 'A'..='z'
+        assert!(key.iter().any(|b| *b < b'A'));
+        assert!(key.iter().any(|b| *b > 127));
+        assert_eq!(16, KeyGenerator::new().take(16).count());
     }
 
     #[test]
     fn test_decode() {
-        let message = decode(EXPECTED_ENCODED, DECODE_KEY).expect("no errors");
+        let message = decode(EXPECTED_ENCODED, DECODE_KEY.as_bytes()).expect("no errors");
         assert_eq!(PLAIN_TEXT, message)
     }
-}
 
+    #[test]
+    fn test_cipher_trait() {
+        let cipher = OneTimePad::new(DECODE_KEY.as_bytes()).expect("valid key");
+        assert_eq!(EXPECTED_ENCODED, cipher.encrypt(PLAIN_TEXT).expect("encrypt"));
+        assert_eq!(PLAIN_TEXT, cipher.decrypt(EXPECTED_ENCODED).expect("decrypt"));
+
+        assert!(OneTimePad::new(b"").is_err());
+        assert!(OneTimePad::new(b"abc").expect("valid key").encrypt("abcd").is_err());
+
+        let generated = OneTimePad::generate(PLAIN_TEXT.len());
+        let encrypted = generated.encrypt(PLAIN_TEXT).expect("encrypt");
+        assert_eq!(PLAIN_TEXT, generated.decrypt(&encrypted).expect("decrypt"));
+    }
+
+    #[test]
+    fn test_bytes() {
+        let message = "Straße 🦀".as_bytes();
+        let key: Vec<u8> = (0..=255u8).rev().take(message.len()).collect();
+        let ciphertext = encrypt(message, &key).expect("key is long enough");
+        assert_eq!(message.len(), ciphertext.len());
+        assert_ne!(message, &ciphertext[..]);
+        assert_eq!(message, &decrypt(&ciphertext, &key).expect("key is long enough")[..]);
 
+        let blob: Vec<u8> = (0..=255u8).collect();
+        assert_eq!(blob, decrypt(&encrypt(&blob, &key.repeat(30)).expect("long key"), &key.repeat(30)).expect("long key"));
+
+        assert!(matches!(encrypt(b"abcd", b"abc"), Err(Error::KeyTooShort { key_len: 3, message_len: 4 })));
+        assert!(matches!(decrypt(b"a", b""), Err(Error::KeyTooShort { key_len: 0, message_len: 1 })));
+        assert!(matches!(decode("not base64!", b"key"), Err(Error::InvalidBase64(_))));
+        let invalid_utf8 = engine().encode(encrypt(&[0xff], &[0]).expect("long enough"));
+        assert!(matches!(decode(&invalid_utf8, &[0]), Err(Error::InvalidUtf8(_))));
+    }
+
+    #[test]
+    fn test_non_ascii_text() {
+        let cipher = OneTimePad::generate(64);
+        let encrypted = cipher.encrypt("Съешь же ещё этих булок, café").expect("encrypt");
+        assert_eq!("Съешь же ещё этих булок, café", cipher.decrypt(&encrypted).expect("decrypt"));
+    }
+}
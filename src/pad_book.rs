@@ -0,0 +1,440 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use rand::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::keygen::os_rng;
+
+const MAGIC: &[u8; 4] = b"OTPB";
+const VERSION: u8 = 1;
+pub const HEADER_LEN: usize = MAGIC.len() + 1 + 8 + 8;
+
+/// Location of the pad bytes a message was encrypted with, stored in front of
+/// every ciphertext produced by a [`PadBook`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PadHeader {
+    pub offset: u64,
+    pub length: u64,
+}
+
+impl PadHeader {
+    pub fn parse(ciphertext: &[u8]) -> Result<Self> {
+        if ciphertext.len() < HEADER_LEN {
+            return Err(Error::InvalidPad("ciphertext is too short to contain a pad header".to_string()));
+        }
+        if &ciphertext[..4] != MAGIC {
+            return Err(Error::InvalidPad("ciphertext was not produced by a pad book".to_string()));
+        }
+        if ciphertext[4] != VERSION {
+            return Err(Error::InvalidPad(format!("unsupported pad book version {}", ciphertext[4])));
+        }
+        let offset = u64::from_be_bytes(ciphertext[5..13].try_into().expect("8 bytes"));
+        let length = u64::from_be_bytes(ciphertext[13..21].try_into().expect("8 bytes"));
+        if length != (ciphertext.len() - HEADER_LEN) as u64 {
+            return Err(Error::InvalidPad("pad header length does not match the ciphertext".to_string()));
+        }
+        Ok(Self { offset, length })
+    }
+
+    fn to_bytes(self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_LEN);
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(VERSION);
+        bytes.extend_from_slice(&self.offset.to_be_bytes());
+        bytes.extend_from_slice(&self.length.to_be_bytes());
+        bytes
+    }
+}
+
+/// Which holder of a pad a [`PadBook`] belongs to. Each party only encrypts
+/// with its own half of the pad, so two holders encrypting at the same time
+/// can never pick the same bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Party {
+    /// Created the pad with [`PadBook::generate`], encrypts with the first half.
+    Generator,
+    /// Received a copy and opened it with [`PadBook::open_copy`], encrypts with the second half.
+    Recipient,
+}
+
+impl Party {
+    fn range(self, pad_len: u64) -> (u64, u64) {
+        match self {
+            Party::Generator => (0, pad_len / 2),
+            Party::Recipient => (pad_len / 2, pad_len),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PadState {
+    party: Party,
+    // sorted, non overlapping and non adjacent [start, end) byte ranges
+    consumed: Vec<(u64, u64)>,
+}
+
+impl PadState {
+    fn new(party: Party) -> Self {
+        Self { party, consumed: Vec::new() }
+    }
+
+    fn mark(&mut self, start: u64, end: u64) {
+        if start == end {
+            return;
+        }
+        self.consumed.push((start, end));
+        self.consumed.sort();
+        let mut merged: Vec<(u64, u64)> = Vec::with_capacity(self.consumed.len());
+        for (start, end) in self.consumed.drain(..) {
+            match merged.last_mut() {
+                Some(last) if start <= last.1 => last.1 = last.1.max(end),
+                _ => merged.push((start, end)),
+            }
+        }
+        self.consumed = merged;
+    }
+
+    fn covers(&self, start: u64, end: u64) -> bool {
+        start == end || self.consumed.iter().any(|(s, e)| *s <= start && end <= *e)
+    }
+
+    // first unused run of `length` bytes in this party's half
+    fn allocate(&self, length: u64, pad_len: u64) -> Option<u64> {
+        let (low, high) = self.party.range(pad_len);
+        let mut cursor = low;
+        for (start, end) in &self.consumed {
+            if *start >= high {
+                break;
+            }
+            if start.checked_sub(cursor).is_some_and(|gap| gap >= length) {
+                return Some(cursor);
+            }
+            cursor = cursor.max(*end);
+        }
+        high.checked_sub(cursor).is_some_and(|gap| gap >= length).then_some(cursor)
+    }
+
+    // the state file is not trusted: every range must lie inside the pad, in order
+    fn validate(&self, pad_len: u64) -> Result<()> {
+        let mut previous_end = 0;
+        for (start, end) in &self.consumed {
+            if start >= end || *end > pad_len || *start < previous_end {
+                return Err(Error::InvalidPad(format!("pad state range {}..{} is invalid for a {} byte pad", start, end, pad_len)));
+            }
+            previous_end = *end;
+        }
+        Ok(())
+    }
+
+    // unused bytes in this party's half
+    fn remaining(&self, pad_len: u64) -> u64 {
+        let (low, high) = self.party.range(pad_len);
+        let used: u64 = self.consumed.iter().
+            map(|(start, end)| (*end).min(high).saturating_sub((*start).max(low))).
+            sum();
+        (high - low).saturating_sub(used)
+    }
+}
+
+/// A large one time pad on disk together with a sidecar state file recording
+/// which byte ranges have already been used. Every message gets a fresh range
+/// and the state is saved before the ciphertext is handed out, so pad bytes
+/// are never used twice, even across restarts.
+#[derive(Debug)]
+pub struct PadBook {
+    pad_path: PathBuf,
+    state_path: PathBuf,
+    pad_len: u64,
+    state: PadState,
+}
+
+impl PadBook {
+    /// Writes a new pad of `size` bytes from the operating system's CSPRNG.
+    pub fn generate<P: AsRef<Path>>(pad_path: P, size: u64) -> Result<Self> {
+        Self::generate_with_rng(pad_path, size, &mut os_rng())
+    }
+
+    /// Writes a new pad of `size` bytes. Refuses to overwrite an existing pad.
+    pub fn generate_with_rng<P: AsRef<Path>, R: RngCore + CryptoRng + ?Sized>(pad_path: P, size: u64, rng: &mut R) -> Result<Self> {
+        let pad_path = pad_path.as_ref();
+        let file = OpenOptions::new().write(true).create_new(true).open(pad_path)?;
+        let mut writer = BufWriter::new(file);
+        let mut chunk = [0u8; 64 * 1024];
+        let mut remaining = size;
+        while remaining > 0 {
+            let n = remaining.min(chunk.len() as u64) as usize;
+            rng.fill_bytes(&mut chunk[..n]);
+            writer.write_all(&chunk[..n])?;
+            remaining -= n as u64;
+        }
+        writer.flush()?;
+
+        let book = Self {
+            pad_path: pad_path.to_path_buf(),
+            state_path: Self::state_path(pad_path),
+            pad_len: size,
+            state: PadState::new(Party::Generator),
+        };
+        book.save()?;
+        Ok(book)
+    }
+
+    /// Opens a pad together with its consumption state. Fails if the state
+    /// file is missing: starting over would hand out used pad bytes again.
+    pub fn open<P: AsRef<Path>>(pad_path: P) -> Result<Self> {
+        let pad_path = pad_path.as_ref();
+        let pad_len = std::fs::metadata(pad_path)?.len();
+        let state_path = Self::state_path(pad_path);
+        if !state_path.exists() {
+            return Err(Error::InvalidPad(format!(
+                "pad state {} is missing; use PadBook::open_copy only for a pad that was never used here", state_path.display()
+            )));
+        }
+        let state: PadState = serde_json::from_slice(&std::fs::read(&state_path)?).
+            map_err(|e| Error::InvalidPad(format!("corrupt pad state {}: {}", state_path.display(), e)))?;
+        state.validate(pad_len)?;
+        Ok(Self { pad_path: pad_path.to_path_buf(), state_path, pad_len, state })
+    }
+
+    /// Starts tracking a freshly received copy of a pad with empty state, as
+    /// the [`Party::Recipient`]. Refuses if the pad already has a state file.
+    pub fn open_copy<P: AsRef<Path>>(pad_path: P) -> Result<Self> {
+        let pad_path = pad_path.as_ref();
+        let pad_len = std::fs::metadata(pad_path)?.len();
+        let state_path = Self::state_path(pad_path);
+        if state_path.exists() {
+            return Err(Error::InvalidPad(format!("pad state {} already exists, use PadBook::open", state_path.display())));
+        }
+        let book = Self { pad_path: pad_path.to_path_buf(), state_path, pad_len, state: PadState::new(Party::Recipient) };
+        book.save()?;
+        Ok(book)
+    }
+
+    /// The sidecar state file of a pad, `<pad>.state`.
+    pub fn state_path<P: AsRef<Path>>(pad_path: P) -> PathBuf {
+        let mut path = pad_path.as_ref().as_os_str().to_owned();
+        path.push(".state");
+        PathBuf::from(path)
+    }
+
+    pub fn len(&self) -> u64 {
+        self.pad_len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pad_len == 0
+    }
+
+    pub fn party(&self) -> Party {
+        self.state.party
+    }
+
+    /// Byte ranges used by either party that this book knows of.
+    pub fn consumed(&self) -> Vec<Range<u64>> {
+        self.state.consumed.iter().map(|(start, end)| *start..*end).collect()
+    }
+
+    /// Unused bytes left for this party to encrypt with.
+    pub fn remaining(&self) -> u64 {
+        self.state.remaining(self.pad_len)
+    }
+
+    /// Encrypts `message` with the first unused run of this party's pad bytes,
+    /// marking them consumed before returning `header || ciphertext`.
+    pub fn encrypt(&mut self, message: &[u8]) -> Result<Vec<u8>> {
+        let length = message.len() as u64;
+        let offset = self.state.allocate(length, self.pad_len).
+            ok_or(Error::PadExhausted { requested: length, remaining: self.remaining() })?;
+
+        let key = self.read_pad(offset, length)?;
+        self.state.mark(offset, offset + length);
+        self.save()?;
+
+        let mut ciphertext = PadHeader { offset, length }.to_bytes();
+        ciphertext.extend(crate::one_time_pad::encrypt(message, &key)?);
+        Ok(ciphertext)
+    }
+
+    /// Decrypts a ciphertext produced by [`PadBook::encrypt`] on a copy of this
+    /// pad. The pad bytes it used are marked consumed so this book never
+    /// encrypts with them.
+    pub fn decrypt(&mut self, ciphertext: &[u8]) -> Result<Vec<u8>> {
+        let header = PadHeader::parse(ciphertext)?;
+        let key = self.read_pad(header.offset, header.length)?;
+        let end = header.offset + header.length;
+        if !self.state.covers(header.offset, end) {
+            self.state.mark(header.offset, end);
+            self.save()?;
+        }
+        crate::one_time_pad::decrypt(&ciphertext[HEADER_LEN..], &key)
+    }
+
+    fn read_pad(&self, offset: u64, length: u64) -> Result<Vec<u8>> {
+        let end = offset.checked_add(length).
+            filter(|end| *end <= self.pad_len).
+            ok_or_else(|| Error::InvalidPad(format!("pad range {}..{} is outside of the {} byte pad", offset, offset.saturating_add(length), self.pad_len)))?;
+        let mut file = File::open(&self.pad_path)?;
+        file.seek(SeekFrom::Start(offset))?;
+        let mut key = vec![0u8; (end - offset) as usize];
+        file.read_exact(&mut key)?;
+        Ok(key)
+    }
+
+    // written to a temporary file first so a crash never leaves a truncated state behind
+    fn save(&self) -> Result<()> {
+        let mut tmp = self.state_path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        let state = serde_json::to_vec(&self.state).
+            map_err(|e| Error::InvalidPad(format!("failed to serialize pad state: {}", e)))?;
+        std::fs::write(&tmp, state)?;
+        std::fs::rename(&tmp, &self.state_path)?;
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    use super::*;
+
+    fn temp_pad(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("rusty_cryptography_kit_{}_{}.pad", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(PadBook::state_path(&path));
+        path
+    }
+
+    fn cleanup(paths: &[&PathBuf]) {
+        for path in paths {
+            let _ = std::fs::remove_file(path);
+            let _ = std::fs::remove_file(PadBook::state_path(path));
+        }
+    }
+
+    #[test]
+    fn test_exchange() {
+        let sender_path = temp_pad("sender");
+        let receiver_path = temp_pad("receiver");
+        let mut sender = PadBook::generate_with_rng(&sender_path, 1024, &mut StdRng::seed_from_u64(1)).expect("generate");
+        std::fs::copy(&sender_path, &receiver_path).expect("copy pad");
+        assert!(matches!(PadBook::open(&receiver_path), Err(Error::InvalidPad(_))), "a copy has no state yet");
+        let mut receiver = PadBook::open_copy(&receiver_path).expect("open copy");
+        assert!(matches!(PadBook::open_copy(&receiver_path), Err(Error::InvalidPad(_))), "the copy has state now");
+
+        let first = sender.encrypt(b"attack at dawn").expect("encrypt");
+        let second = sender.encrypt("retreat at dusk 🦀".as_bytes()).expect("encrypt");
+        assert_eq!(PadHeader { offset: 0, length: 14 }, PadHeader::parse(&first).expect("header"));
+        assert_eq!(PadHeader { offset: 14, length: 20 }, PadHeader::parse(&second).expect("header"));
+        assert_eq!(vec![0..34], sender.consumed());
+        assert_eq!(512 - 34, sender.remaining());
+        assert_eq!(Party::Generator, sender.party());
+        assert_eq!(Party::Recipient, receiver.party());
+
+        // messages may arrive out of order
+        assert_eq!("retreat at dusk 🦀".as_bytes(), &receiver.decrypt(&second).expect("decrypt")[..]);
+        assert_eq!(b"attack at dawn", &receiver.decrypt(&first).expect("decrypt")[..]);
+        assert_eq!(vec![0..34], receiver.consumed());
+
+        let reply = receiver.encrypt(b"ack").expect("encrypt");
+        assert_eq!(512, PadHeader::parse(&reply).expect("header").offset);
+        assert_eq!(b"ack", &sender.decrypt(&reply).expect("decrypt")[..]);
+        assert_eq!(vec![0..34, 512..515], sender.consumed());
+
+        // the party survives reopening
+        assert_eq!(Party::Recipient, PadBook::open(&receiver_path).expect("open").party());
+
+        cleanup(&[&sender_path, &receiver_path]);
+    }
+
+    #[test]
+    fn test_never_reuses_bytes() {
+        let path = temp_pad("reuse");
+        let mut book = PadBook::generate_with_rng(&path, 32, &mut StdRng::seed_from_u64(2)).expect("generate");
+        assert!(PadBook::generate(&path, 32).is_err(), "existing pads are never overwritten");
+
+        // the generator owns the first 16 bytes
+        book.encrypt(&[0u8; 10]).expect("encrypt");
+        // the state survives reopening the pad
+        let mut book = PadBook::open(&path).expect("open");
+        assert_eq!(vec![0..10], book.consumed());
+        let err = book.encrypt(&[0u8; 7]).expect_err("only 6 bytes left");
+        assert!(matches!(err, Error::PadExhausted { requested: 7, remaining: 6 }));
+        let last = book.encrypt(&[0u8; 6]).expect("exactly fits");
+        assert_eq!(10, PadHeader::parse(&last).expect("header").offset);
+        assert_eq!(0, book.remaining());
+        assert!(book.encrypt(b"x").is_err());
+
+        // losing the state must not silently start over
+        std::fs::remove_file(PadBook::state_path(&path)).expect("remove state");
+        assert!(matches!(PadBook::open(&path), Err(Error::InvalidPad(_))));
+
+        // nor may a tampered state file point outside of the pad or overlap itself
+        for consumed in ["[[0,40]]", "[[5,5]]", "[[0,10],[8,12]]", "[[20,30],[0,10]]"] {
+            let state = format!(r#"{{"party":"Generator","consumed":{}}}"#, consumed);
+            std::fs::write(PadBook::state_path(&path), state).expect("write state");
+            assert!(matches!(PadBook::open(&path), Err(Error::InvalidPad(_))), "{}", consumed);
+        }
+
+        cleanup(&[&path]);
+    }
+
+    #[test]
+    fn test_both_encrypt_first() {
+        let sender_path = temp_pad("first_sender");
+        let receiver_path = temp_pad("first_receiver");
+        let mut sender = PadBook::generate_with_rng(&sender_path, 64, &mut StdRng::seed_from_u64(3)).expect("generate");
+        std::fs::copy(&sender_path, &receiver_path).expect("copy pad");
+        let mut receiver = PadBook::open_copy(&receiver_path).expect("open copy");
+
+        // neither side has seen a message from the other yet
+        let from_sender = sender.encrypt(b"meet at noon").expect("encrypt");
+        let from_receiver = receiver.encrypt(b"meet at nine").expect("encrypt");
+        let sender_header = PadHeader::parse(&from_sender).expect("header");
+        let receiver_header = PadHeader::parse(&from_receiver).expect("header");
+        assert_eq!(PadHeader { offset: 0, length: 12 }, sender_header);
+        assert_eq!(PadHeader { offset: 32, length: 12 }, receiver_header);
+
+        assert_eq!(b"meet at noon", &receiver.decrypt(&from_sender).expect("decrypt")[..]);
+        assert_eq!(b"meet at nine", &sender.decrypt(&from_receiver).expect("decrypt")[..]);
+        assert_eq!(vec![0..12, 32..44], sender.consumed());
+        assert_eq!(sender.consumed(), receiver.consumed());
+
+        cleanup(&[&sender_path, &receiver_path]);
+    }
+
+    #[test]
+    fn test_gaps() {
+        let mut state = PadState::new(Party::Generator);
+        state.mark(10, 20);
+        state.mark(30, 40);
+        state.mark(20, 25);
+        assert_eq!(vec![(10, 25), (30, 40)], state.consumed);
+        assert_eq!(Some(0), state.allocate(10, 200));
+        state.mark(0, 10);
+        assert_eq!(vec![(0, 25), (30, 40)], state.consumed);
+        assert_eq!(Some(25), state.allocate(5, 200));
+        assert_eq!(Some(40), state.allocate(11, 200));
+        assert_eq!(None, state.allocate(61, 200));
+        assert_eq!(65, state.remaining(200));
+        assert!(state.covers(12, 25));
+        assert!(!state.covers(24, 26));
+
+        // the recipient skips over the generator's half and ranges straddling it
+        let mut state = PadState::new(Party::Recipient);
+        state.mark(0, 10);
+        state.mark(90, 110);
+        assert_eq!(Some(110), state.allocate(90, 200));
+        assert_eq!(None, state.allocate(91, 200));
+        assert_eq!(90, state.remaining(200));
+
+        assert!(PadHeader::parse(b"OTPB").is_err());
+        assert!(PadHeader::parse(&[0u8; HEADER_LEN]).is_err());
+    }
+}
@@ -0,0 +1,378 @@
+use std::fmt;
+
+use rand::Rng;
+use rand::seq::SliceRandom;
+
+use crate::alphabet::ASCII_LETTERS;
+use crate::candidate::Candidate;
+use crate::cipher::Cipher;
+use crate::detection::detect_english::Detector;
+use crate::detection::ngram::{Fitness, NgramScorer};
+use crate::error::{Error, Result};
+
+const SIZE: usize = 5;
+const CELLS: usize = SIZE * SIZE;
+// annealing iterations at every temperature step
+const ITERATIONS: usize = 3000;
+const TEMPERATURE_STEP: f64 = 0.2;
+// average quadgram fitness of a confirmed decryption: English scores around
+// -4.5, squares the annealing got stuck on -5.5 or below
+const ENGLISH_FITNESS: f64 = -5.0;
+// x, and q to split a doubled x
+const FILLER: usize = 23;
+const OTHER_FILLER: usize = 16;
+
+/// The 5×5 Playfair square. One letter has to go to fit 26 letters in 25
+/// cells; it is merged into another one, `j` into `i` unless configured.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Square {
+    // letter (0 for a) in every cell, row by row
+    letters: [usize; CELLS],
+    // cell of every letter, the merged letter shares the cell of the letter it is merged into
+    cells: [usize; 26],
+    merged: usize,
+    into: usize,
+}
+
+impl Square {
+    /// Square of the distinct letters of `keyword` followed by the rest of
+    /// the alphabet, with `j` merged into `i`.
+    pub fn new(keyword: &str) -> Self {
+        Self::with_merge(keyword, 'j', 'i').expect("j and i are distinct letters")
+    }
+
+    /// Like [`Square::new`], merging the letter `merged` into `into`.
+    pub fn with_merge(keyword: &str, merged: char, into: char) -> Result<Self> {
+        let (merged, into) = match (ASCII_LETTERS.index_of(merged), ASCII_LETTERS.index_of(into)) {
+            (Some(merged), Some(into)) if merged != into => (merged, into),
+            _ => return Err(Error::InvalidKey(format!(
+                "playfair must merge one letter into another, got {:?} into {:?}", merged, into
+            ))),
+        };
+        let mut letters = Vec::with_capacity(CELLS);
+        for letter in ASCII_LETTERS.indices(keyword).into_iter().chain(0..26) {
+            let letter = if letter == merged { into } else { letter };
+            if !letters.contains(&letter) {
+                letters.push(letter);
+            }
+        }
+        Ok(Self::from_cells(letters.try_into().expect("25 letters"), merged, into))
+    }
+
+    fn from_cells(letters: [usize; CELLS], merged: usize, into: usize) -> Self {
+        let mut cells = [0; 26];
+        for (cell, &letter) in letters.iter().enumerate() {
+            cells[letter] = cell;
+        }
+        cells[merged] = cells[into];
+        Self { letters, cells, merged, into }
+    }
+
+    /// The 25 letters row by row, a keyword that rebuilds this very square.
+    pub fn letters(&self) -> String {
+        self.letters.iter().map(|&letter| ASCII_LETTERS.symbols()[letter]).collect()
+    }
+
+    // the pair of letters `a` and `b` turn into, encoding or decoding
+    fn pair(&self, a: usize, b: usize, encode: bool) -> (usize, usize) {
+        let (a, b) = (self.cells[a], self.cells[b]);
+        let (row_a, column_a, row_b, column_b) = (a / SIZE, a % SIZE, b / SIZE, b % SIZE);
+        let step = if encode { 1 } else { SIZE - 1 };
+        let (a, b) = if row_a == row_b {
+            (row_a * SIZE + (column_a + step) % SIZE, row_b * SIZE + (column_b + step) % SIZE)
+        } else if column_a == column_b {
+            ((row_a + step) % SIZE * SIZE + column_a, (row_b + step) % SIZE * SIZE + column_b)
+        } else {
+            (row_a * SIZE + column_b, row_b * SIZE + column_a)
+        };
+        (self.letters[a], self.letters[b])
+    }
+}
+
+impl fmt::Display for Square {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let letters = self.letters().to_uppercase();
+        for (i, row) in letters.as_bytes().chunks(SIZE).enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            let row: Vec<String> = row.iter().map(|&ch| (ch as char).to_string()).collect();
+            write!(f, "{}", row.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Encodes with the square of `keyword` (`j` merged into `i`) and `x` as the
+/// filler, see [`Playfair`].
+pub fn encode(message: &str, keyword: &str) -> Result<String> {
+    Playfair::new(keyword).encrypt(message)
+}
+
+
+pub fn decode(message: &str, keyword: &str) -> Result<String> {
+    Playfair::new(keyword).decrypt(message)
+}
+
+
+/// Recovers the square (`j` merged into `i`) of an English message with
+/// [`crack_with_rng`], using the thread local generator and 5 restarts.
+pub fn crack(message: &str) -> Result<Candidate<String>> {
+    crack_with_rng(message, &Square::new(""), &mut rand::rng(), 5)
+}
+
+
+/// Ciphertext-only attack on a square that merges the same letter as
+/// `template` (only its merge is used, e.g. `Square::with_merge("", 'q', 'k')`):
+/// simulated annealing over squares, scored by the quadgram fitness of the
+/// decryption. Each result is confirmed by its average quadgram fitness and by
+/// re-inserting word breaks and checking it with [`Detector::is_english`]; up
+/// to `restarts` further attempts are made from new random squares until one
+/// is confirmed. The key of the result is the
+/// square's letters, the decoded message has fillers removed and word breaks
+/// restored. Needs a few hundred letters.
+pub fn crack_with_rng<R: Rng + ?Sized>(message: &str, template: &Square, rng: &mut R, restarts: usize) -> Result<Candidate<String>> {
+    let letters = ASCII_LETTERS.indices(message);
+    if letters.len() < 2 || !letters.len().is_multiple_of(2) {
+        return Err(Error::InvalidArgument("playfair ciphertext must have an even number of letters".to_string()));
+    }
+    let scorer = NgramScorer::english_quadgrams()?;
+    let detector = Detector::new_with_fix_db()?;
+    // the filler Playfair::with_merge picks for this merge
+    let filler = if template.merged == FILLER { OTHER_FILLER } else { FILLER };
+
+    for _ in 0..=restarts {
+        let mut cells = template.letters;
+        cells.shuffle(rng);
+        let (square, fitness) = anneal(&letters, Square::from_cells(cells, template.merged, template.into), scorer, rng);
+        let plain: String = decode_letters(&letters, &square).iter().map(|&i| ASCII_LETTERS.symbols()[i]).collect();
+        let segmented = detector.segment(&strip_fillers(&plain, ASCII_LETTERS.symbols()[filler]));
+        if scorer.score_per_ngram(&plain) > ENGLISH_FITNESS && detector.is_english(&segmented) {
+            return Ok(Candidate::new(square.letters(), segmented, fitness));
+        }
+    }
+    Err(Error::ExhaustedSearch)
+}
+
+fn anneal<R: Rng + ?Sized>(letters: &[usize], mut parent: Square, scorer: &impl Fitness, rng: &mut R) -> (Square, f64) {
+    let mut parent_fitness = scorer.fitness(&decode_letters(letters, &parent));
+    let mut best = (parent.clone(), parent_fitness);
+    // the usual starting temperature for quadgram scores, higher for longer texts
+    let mut temperature = 10.0 + 0.087 * (letters.len() as f64 - 84.0).max(0.0);
+    while temperature > 0.0 {
+        for _ in 0..ITERATIONS {
+            let child = Square::from_cells(mutate(parent.letters, rng), parent.merged, parent.into);
+            let fitness = scorer.fitness(&decode_letters(letters, &child));
+            let delta = fitness - parent_fitness;
+            if delta > 0.0 || rng.random::<f64>() < (delta / temperature).exp() {
+                parent = child;
+                parent_fitness = fitness;
+                if parent_fitness > best.1 {
+                    best = (parent.clone(), parent_fitness);
+                }
+            }
+        }
+        temperature -= TEMPERATURE_STEP;
+    }
+    best
+}
+
+// mostly swaps two letters, sometimes rearranges whole rows or columns
+fn mutate<R: Rng + ?Sized>(mut cells: [usize; CELLS], rng: &mut R) -> [usize; CELLS] {
+    let (a, b) = (rng.random_range(0..SIZE), rng.random_range(0..SIZE));
+    match rng.random_range(0..50) {
+        0 => for column in 0..SIZE {
+            cells.swap(a * SIZE + column, b * SIZE + column);
+        },
+        1 => for row in 0..SIZE {
+            cells.swap(row * SIZE + a, row * SIZE + b);
+        },
+        2 => cells.reverse(),
+        3 => for row in cells.chunks_mut(SIZE) {
+            row.reverse();
+        },
+        _ => cells.swap(rng.random_range(0..CELLS), rng.random_range(0..CELLS)),
+    }
+    cells
+}
+
+fn decode_letters(letters: &[usize], square: &Square) -> Vec<usize> {
+    let mut plain = Vec::with_capacity(letters.len());
+    for pair in letters.chunks_exact(2) {
+        let (a, b) = square.pair(pair[0], pair[1], false);
+        plain.push(a);
+        plain.push(b);
+    }
+    plain
+}
+
+// drops fillers between doubled letters ("balxloon") and at the very end
+fn strip_fillers(text: &str, filler: char) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut stripped = String::with_capacity(text.len());
+    for (i, &ch) in chars.iter().enumerate() {
+        let between_doubles = i % 2 == 1 && i + 1 < chars.len() && chars[i - 1] == chars[i + 1];
+        let last = i + 1 == chars.len();
+        if ch == filler && (between_doubles || last) {
+            continue;
+        }
+        stripped.push(ch);
+    }
+    stripped
+}
+
+/// Playfair enciphers letter pairs (digraphs) with a keyword square. Before
+/// enciphering, the merged letter is replaced, everything but letters is
+/// dropped, a filler is inserted between doubled letters of a pair and the
+/// text is padded to an even length. Ciphertext is upper case, decrypted text
+/// lower case with the fillers left in.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Playfair {
+    key: String,
+    square: Square,
+    filler: usize,
+}
+
+impl Playfair {
+    pub fn new(keyword: &str) -> Self {
+        Self { key: keyword.to_string(), square: Square::new(keyword), filler: FILLER }
+    }
+
+    pub fn with_merge(keyword: &str, merged: char, into: char) -> Result<Self> {
+        let square = Square::with_merge(keyword, merged, into)?;
+        let filler = if square.merged == FILLER { OTHER_FILLER } else { FILLER };
+        Ok(Self { key: keyword.to_string(), square, filler })
+    }
+
+    /// Uses `filler` instead of `x` to split doubled letters and pad the text.
+    /// A doubled filler is split with `q`, or `x` if the filler is `q`.
+    pub fn with_filler(mut self, filler: char) -> Result<Self> {
+        match ASCII_LETTERS.index_of(filler) {
+            Some(filler) if filler != self.square.merged => self.filler = filler,
+            _ => return Err(Error::InvalidArgument(format!("{:?} cannot be used as the filler", filler))),
+        }
+        Ok(self)
+    }
+
+    pub fn square(&self) -> &Square {
+        &self.square
+    }
+
+    // the digraphs to encipher
+    fn prepare(&self, message: &str) -> Vec<usize> {
+        let filler = self.filler;
+        // a doubled filler is split with another letter
+        let other = if filler == OTHER_FILLER { FILLER } else { OTHER_FILLER };
+        let mut letters = ASCII_LETTERS.indices(message).into_iter().
+            map(|letter| if letter == self.square.merged { self.square.into } else { letter }).
+            peekable();
+        let mut prepared = Vec::new();
+        while let Some(a) = letters.next() {
+            let b = match letters.peek() {
+                Some(&b) if b != a => {
+                    letters.next();
+                    b
+                }
+                _ if a == filler => other,
+                _ => filler,
+            };
+            prepared.push(a);
+            prepared.push(b);
+        }
+        prepared
+    }
+}
+
+impl Cipher for Playfair {
+    type Key = String;
+
+    fn validate_key(_key: &String) -> Result<()> {
+        // letters outside of the square are ignored, any keyword makes a square
+        Ok(())
+    }
+
+    fn key(&self) -> &String {
+        &self.key
+    }
+
+    fn encrypt(&self, message: &str) -> Result<String> {
+        let mut encoded = String::new();
+        for pair in self.prepare(message).chunks_exact(2) {
+            let (a, b) = self.square.pair(pair[0], pair[1], true);
+            encoded.push(ASCII_LETTERS.symbols()[a].to_ascii_uppercase());
+            encoded.push(ASCII_LETTERS.symbols()[b].to_ascii_uppercase());
+        }
+        Ok(encoded)
+    }
+
+    fn decrypt(&self, message: &str) -> Result<String> {
+        let letters = ASCII_LETTERS.indices(message);
+        if !letters.len().is_multiple_of(2) {
+            return Err(Error::InvalidArgument("playfair ciphertext must have an even number of letters".to_string()));
+        }
+        Ok(decode_letters(&letters, &self.square).iter().map(|&i| ASCII_LETTERS.symbols()[i]).collect())
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    use super::*;
+
+    #[test]
+    fn test_square() {
+        let square = Square::new("playfair example");
+        assert_eq!("playfirexmbcdghknoqstuvwz", square.letters());
+        assert_eq!("P L A Y F\nI R E X M\nB C D G H\nK N O Q S\nT U V W Z", square.to_string());
+        assert_eq!(square, Square::new(&square.letters()));
+
+        let without_q = Square::with_merge("keyword", 'q', 'k').expect("distinct letters");
+        assert!(without_q.letters().contains('j'));
+        assert!(!without_q.letters().contains('q'));
+        assert!(Square::with_merge("keyword", 'q', 'q').is_err());
+    }
+
+    #[test]
+    fn test_encode() {
+        let encoded = encode("Hide the gold in the tree stump", "playfair example").expect("encode");
+        assert_eq!("BMODZBXDNABEKUDMUIXMMOUVIF", encoded);
+        assert_eq!("hidethegoldinthetrexestump", decode(&encoded, "playfair example").expect("decode"));
+
+        // j is merged into i, the doubled x is split with q
+        let cipher = Playfair::new("monarchy");
+        assert_eq!("ixixxq", cipher.decrypt(&cipher.encrypt("Jixx").expect("encrypt")).expect("decrypt"));
+
+        let cipher = Playfair::new("monarchy").with_filler('z').expect("valid filler");
+        assert_eq!("balzloon", cipher.decrypt(&cipher.encrypt("balloon").expect("encrypt")).expect("decrypt"));
+        assert!(Playfair::new("monarchy").with_filler('j').is_err());
+
+        assert!(matches!(decode("ABC", "monarchy"), Err(Error::InvalidArgument(_))));
+        assert!(matches!(crack("ABC"), Err(Error::InvalidArgument(_))));
+    }
+
+    #[test]
+    fn test_strip_fillers() {
+        assert_eq!("balloon", strip_fillers("balxloon", 'x'));
+        assert_eq!("balloons", strip_fillers("balxloonsx", 'x'));
+        // only fillers in the second half of a pair can have been inserted
+        assert_eq!("exxon", strip_fillers("exxon", 'x'));
+    }
+
+    #[test]
+    fn test_crack() {
+        let message = "On offering to help the blind man, the man who then stole his car, had not, at that precise moment,
+  had any evil intention, quite the contrary, what he did was nothing more than obey those feelings of generosity and altruism.";
+        // q merged into k, the search has to be told
+        let cipher = Playfair::with_merge("hidden treasure", 'q', 'k').expect("distinct letters");
+        let encoded = cipher.encrypt(message).expect("encrypt");
+        let template = Square::with_merge("", 'q', 'k').expect("distinct letters");
+        let cracked = crack_with_rng(&encoded, &template, &mut StdRng::seed_from_u64(1), 1).expect("confirmed as english");
+        assert!(cracked.decoded_message.starts_with("on offering to help the blind man"), "{}", cracked.decoded_message);
+        assert!(!cracked.key.contains('q'));
+        let recovered = Playfair::with_merge(&cracked.key, 'q', 'k').expect("distinct letters");
+        assert_eq!(cipher.decrypt(&encoded).expect("decrypt"), recovered.decrypt(&encoded).expect("decrypt"));
+    }
+}
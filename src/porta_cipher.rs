@@ -0,0 +1,206 @@
+use std::io;
+
+use rand::{CryptoRng, RngCore};
+
+use crate::alphabet::ASCII_LETTERS;
+use crate::cipher::Cipher;
+use crate::error::{Error, Result};
+use crate::keygen::GenerateKey;
+use crate::stream::{CharTransform, StreamingCipher, TextTransform};
+use crate::vigenere_cipher::{statistical_key, StatisticalResponse};
+
+/// Half the alphabet: letters pair up across the halves, and key letters pair
+/// up two by two (A/B, C/D, ...) into this many tableaux.
+const HALF: usize = 13;
+
+/// Porta is its own inverse, `decode` is the same as `encode`.
+pub fn encode(message: &str, key: &str) -> Result<String> {
+    Ok(handle(message, &pairs(key)?))
+}
+
+
+pub fn decode(message: &str, key: &str) -> Result<String> {
+    Ok(handle(message, &pairs(key)?))
+}
+
+
+/// Recovers the key of an English message, like
+/// [`crate::vigenere_cipher::decode_statistical`]: estimates the key length,
+/// picks the tableau of every column by letter frequencies, then refines the
+/// key letter by letter with quadgram fitness. Each key letter is reported as
+/// the first of its pair, so `"fortification"` comes back as
+/// `"eoqsieicasiom"`, which encrypts the same.
+pub fn decode_statistical(message: &str, max_key_length: usize) -> Result<StatisticalResponse> {
+    let key: String = statistical_key(message, max_key_length, HALF, swap)?.iter().
+        map(|&pair| ASCII_LETTERS.symbols()[2 * pair]).
+        collect();
+    Ok(StatisticalResponse { decoded_message: decode(message, &key)?, key })
+}
+
+// the tableau (0 to 12) of every letter of the key
+fn pairs(key: &str) -> Result<Vec<usize>> {
+    let pairs: Vec<usize> = ASCII_LETTERS.indices(key).iter().map(|letter| letter / 2).collect();
+    if pairs.is_empty() {
+        return Err(Error::InvalidKey("porta key must contain at least one ascii letter".to_string()));
+    }
+    Ok(pairs)
+}
+
+// exchanges `letter` with its partner in the other half of the alphabet under tableau `pair`
+fn swap(letter: usize, pair: usize) -> usize {
+    if letter < HALF {
+        HALF + (letter + pair) % HALF
+    } else {
+        (letter - pair) % HALF
+    }
+}
+
+fn substitute(ch: char, pair: usize) -> char {
+    match ASCII_LETTERS.index_of(ch) {
+        Some(letter) => ASCII_LETTERS.symbol_like(swap(letter, pair), ch),
+        None => ch,
+    }
+}
+
+fn handle(message: &str, pairs: &[usize]) -> String {
+    let mut pairs = pairs.iter().cycle();
+    message.chars().
+        map(|ch| match ASCII_LETTERS.contains(ch) {
+            true => substitute(ch, *pairs.next().expect("key is not empty")),
+            false => ch,
+        }).
+        collect()
+}
+
+/// Della Porta cipher: every key letter selects one of 13 tableaux that swap
+/// the first half of the alphabet with the second, so encryption and
+/// decryption are the same. Characters other than ASCII letters pass through
+/// and do not use up key letters.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Porta {
+    key: String,
+    pairs: Vec<usize>,
+}
+
+impl Porta {
+    pub fn new(key: &str) -> Result<Self> {
+        Ok(Self { key: key.to_string(), pairs: pairs(key)? })
+    }
+}
+
+impl Cipher for Porta {
+    type Key = String;
+
+    fn validate_key(key: &String) -> Result<()> {
+        pairs(key).map(|_| ())
+    }
+
+    fn key(&self) -> &String {
+        &self.key
+    }
+
+    fn encrypt(&self, message: &str) -> Result<String> {
+        Ok(handle(message, &self.pairs))
+    }
+
+    fn decrypt(&self, message: &str) -> Result<String> {
+        Ok(handle(message, &self.pairs))
+    }
+}
+
+impl StreamingCipher for Porta {
+    type Encryptor = TextTransform<PortaStream>;
+    type Decryptor = TextTransform<PortaStream>;
+
+    fn encryptor(&self) -> Self::Encryptor {
+        TextTransform::new(PortaStream::new(self.pairs.clone()))
+    }
+
+    fn decryptor(&self) -> Self::Decryptor {
+        TextTransform::new(PortaStream::new(self.pairs.clone()))
+    }
+}
+
+/// Character by character form of the Porta cipher that remembers its
+/// position in the key, used by the streaming adapters.
+pub struct PortaStream {
+    pairs: Vec<usize>,
+    next_pair: usize,
+}
+
+impl PortaStream {
+    fn new(pairs: Vec<usize>) -> Self {
+        Self { pairs, next_pair: 0 }
+    }
+}
+
+impl CharTransform for PortaStream {
+    fn transform(&mut self, ch: char, output: &mut String) -> io::Result<()> {
+        if !ASCII_LETTERS.contains(ch) {
+            output.push(ch);
+            return Ok(());
+        }
+        output.push(substitute(ch, self.pairs[self.next_pair % self.pairs.len()]));
+        self.next_pair += 1;
+        Ok(())
+    }
+}
+
+impl GenerateKey for Porta {
+    fn generate_key<R: RngCore + CryptoRng + ?Sized>(rng: &mut R, length: usize) -> String {
+        crate::keygen::random_symbols(rng, &ASCII_LETTERS, length.max(1))
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+
+    #[test]
+    fn test_encode() {
+        assert_eq!("SYNNJSCVRNRLAHUTUKUCVRYRLANY", encode("DEFENDTHEEASTWALLOFTHECASTLE", "FORTIFICATION").expect("valid key"));
+        assert_eq!("DEFENDTHEEASTWALLOFTHECASTLE", decode("SYNNJSCVRNRLAHUTUKUCVRYRLANY", "FORTIFICATION").expect("valid key"));
+        // both letters of a pair select the same tableau
+        assert_eq!(encode("Attack at dawn!", "ab").expect("valid key"), encode("Attack at dawn!", "ba").expect("valid key"));
+        assert_eq!("Nggnpx ng qnja!", encode("Attack at dawn!", "a").expect("valid key"));
+        assert!(matches!(encode("text", "123"), Err(Error::InvalidKey(_))));
+    }
+
+    #[test]
+    fn test_reciprocal() {
+        let mut rng = StdRng::seed_from_u64(25);
+        let message = "Be normal, and the crowd will accept you. Straße, Привет 🦀!";
+        for length in 1..12 {
+            let cipher = Porta::new(&Porta::generate_key(&mut rng, length)).expect("generated keys are valid");
+            let encrypted = cipher.encrypt(message).expect("encrypt");
+            assert_eq!(message, cipher.encrypt(&encrypted).expect("encrypt"));
+            assert_eq!(message, cipher.decrypt(&encrypted).expect("decrypt"));
+        }
+        // no letter ever encrypts to itself or to a letter of its own half
+        for pair in 0..HALF {
+            for letter in 0..2 * HALF {
+                assert_eq!(letter, swap(swap(letter, pair), pair));
+                assert_ne!(letter < HALF, swap(letter, pair) < HALF);
+            }
+        }
+    }
+
+    #[test]
+    fn test_decode_statistical() {
+        let message = "On offering to help the blind man, the man who then stole his car, had not, at that precise moment,
+  had any evil intention, quite the contrary, what he did was nothing more than obey those feelings of generosity and altruism which,
+  as everyone knows, are the two best traits of human nature and to be found in much more hardened criminals than this one,
+  a simple car-thief without any hope of advancing in his profession, exploited by the real owners of this enterprise,
+  for it is they who take advantage of the needs of the poor.";
+        for (key, expected) in [("fortification", "eoqsieicasiom"), ("lemon", "kemom"), ("q", "q")] {
+            let response = decode_statistical(&encode(message, key).expect("valid key"), 20).expect("long enough");
+            assert_eq!(expected, response.key);
+            assert_eq!(message, response.decoded_message);
+        }
+        assert!(matches!(decode_statistical("1984", 20), Err(Error::InvalidArgument(_))));
+    }
+}
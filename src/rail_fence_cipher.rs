@@ -0,0 +1,192 @@
+use std::fmt;
+
+use rand::{CryptoRng, Rng, RngCore};
+
+use crate::candidate::{self, Candidate};
+use crate::cipher::Cipher;
+use crate::detection::ngram::NgramScorer;
+use crate::error::{Error, Result};
+use crate::keygen::GenerateKey;
+
+/// Number of rails and how far into the zigzag the message starts. The
+/// zigzag repeats every `2 · (rails - 1)` characters, so that is also the
+/// number of distinct offsets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RailFenceKey {
+    pub rails: usize,
+    pub offset: usize,
+}
+
+impl RailFenceKey {
+    pub fn new(rails: usize, offset: usize) -> Self {
+        Self { rails, offset }
+    }
+
+    fn period(&self) -> usize {
+        2 * (self.rails - 1)
+    }
+
+    // rail of the character at `position`
+    fn rail(&self, position: usize) -> usize {
+        let phase = (position + self.offset) % self.period();
+        if phase < self.rails { phase } else { self.period() - phase }
+    }
+}
+
+impl fmt::Display for RailFenceKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{},{}", self.rails, self.offset)
+    }
+}
+
+/// Writes the message in a zigzag over the rails and reads it off rail by
+/// rail. Every character is transposed, including spaces and punctuation.
+pub fn encode(message: &str, key: RailFenceKey) -> Result<String> {
+    check_key(key)?;
+    let chars: Vec<char> = message.chars().collect();
+    Ok(order(chars.len(), key).into_iter().map(|i| chars[i]).collect())
+}
+
+
+pub fn decode(message: &str, key: RailFenceKey) -> Result<String> {
+    check_key(key)?;
+    let chars: Vec<char> = message.chars().collect();
+    let mut decoded = vec![' '; chars.len()];
+    for (ch, i) in chars.into_iter().zip(order(decoded.len(), key)) {
+        decoded[i] = ch;
+    }
+    Ok(decoded.into_iter().collect())
+}
+
+
+/// Tries every rail count from 2 to `max_rails` with every offset and ranks
+/// the decryptions by quadgram fitness, best first.
+pub fn crack(message: &str, max_rails: usize) -> Result<Vec<Candidate<RailFenceKey>>> {
+    let scorer = NgramScorer::english_quadgrams()?;
+    let max_rails = max_rails.min(message.chars().count());
+    if max_rails < 2 {
+        return Err(Error::InvalidArgument("need at least two rails and two characters to try".to_string()));
+    }
+    let mut candidates = Vec::new();
+    for rails in 2..=max_rails {
+        for offset in 0..2 * (rails - 1) {
+            let key = RailFenceKey::new(rails, offset);
+            let decoded = decode(message, key)?;
+            let score = scorer.score(&decoded);
+            candidates.push(Candidate::new(key, decoded, score));
+        }
+    }
+    candidate::rank(&mut candidates);
+    Ok(candidates)
+}
+
+fn check_key(key: RailFenceKey) -> Result<()> {
+    if key.rails < 2 {
+        return Err(Error::InvalidKey(format!("rail fence needs at least 2 rails, got {}", key.rails)));
+    }
+    // every other use of the period comes after this check
+    if (key.rails - 1).checked_mul(2).is_none() {
+        return Err(Error::InvalidKey(format!("rail fence has too many rails, got {}", key.rails)));
+    }
+    if key.offset >= key.period() {
+        return Err(Error::InvalidKey(format!(
+            "offset must be below {} for {} rails, got {}", key.period(), key.rails, key.offset
+        )));
+    }
+    Ok(())
+}
+
+// positions of the message in the order they are read off the rails
+fn order(length: usize, key: RailFenceKey) -> Vec<usize> {
+    let mut positions: Vec<usize> = (0..length).collect();
+    // stable, so each rail is read left to right
+    positions.sort_by_key(|&i| key.rail(i));
+    positions
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RailFence {
+    key: RailFenceKey,
+}
+
+impl RailFence {
+    pub fn new(key: RailFenceKey) -> Result<Self> {
+        Self::validate_key(&key)?;
+        Ok(Self { key })
+    }
+}
+
+impl Cipher for RailFence {
+    type Key = RailFenceKey;
+
+    fn validate_key(key: &RailFenceKey) -> Result<()> {
+        check_key(*key)
+    }
+
+    fn key(&self) -> &RailFenceKey {
+        &self.key
+    }
+
+    fn encrypt(&self, message: &str) -> Result<String> {
+        encode(message, self.key)
+    }
+
+    fn decrypt(&self, message: &str) -> Result<String> {
+        decode(message, self.key)
+    }
+}
+
+impl GenerateKey for RailFence {
+    // `length` is the highest rail count to draw, at least 3
+    fn generate_key<R: RngCore + CryptoRng + ?Sized>(rng: &mut R, length: usize) -> RailFenceKey {
+        let rails = rng.random_range(2..=length.max(3));
+        RailFenceKey::new(rails, rng.random_range(0..2 * (rails - 1)))
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    use super::*;
+
+    #[test]
+    fn test_encode() {
+        let key = RailFenceKey::new(3, 0);
+        assert_eq!("WECRLTEERDSOEEFEAOCAIVDEN", encode("WEAREDISCOVEREDFLEEATONCE", key).expect("valid key"));
+        assert_eq!("WEAREDISCOVEREDFLEEATONCE", decode("WECRLTEERDSOEEFEAOCAIVDEN", key).expect("valid key"));
+
+        let key = RailFenceKey::new(3, 2);
+        assert_eq!("t dtaka anActw", encode("Attack at dawn", key).expect("valid key"));
+        assert_eq!("Attack at dawn", decode("t dtaka anActw", key).expect("valid key"));
+
+        assert!(matches!(encode("text", RailFenceKey::new(1, 0)), Err(Error::InvalidKey(_))));
+        assert!(matches!(encode("text", RailFenceKey::new(3, 4)), Err(Error::InvalidKey(_))));
+        assert!(matches!(encode("text", RailFenceKey::new(usize::MAX, 0)), Err(Error::InvalidKey(_))));
+        assert_eq!("text", encode("text", RailFenceKey::new(usize::MAX / 2, 0)).expect("period fits"));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut rng = StdRng::seed_from_u64(4);
+        let message = "Привет, 🦀 — the rails go up and down!";
+        for _ in 0..50 {
+            let cipher = RailFence::new(RailFence::generate_key(&mut rng, 12)).expect("generated keys are valid");
+            assert_eq!(message, cipher.decrypt(&cipher.encrypt(message).expect("encrypt")).expect("decrypt"));
+        }
+    }
+
+    #[test]
+    fn test_crack() {
+        let message = "Defend the east wall of the castle until the reinforcements arrive";
+        let candidates = crack(&encode(message, RailFenceKey::new(5, 3)).expect("valid key"), 10).expect("long enough");
+        assert_eq!(RailFenceKey::new(5, 3), candidates[0].key);
+        assert_eq!(message, candidates[0].decoded_message);
+        // 2 + 4 + ... + 18 keys
+        assert_eq!(90, candidates.len());
+
+        assert!(crack("a", 10).is_err());
+    }
+}
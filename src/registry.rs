@@ -0,0 +1,264 @@
+use std::collections::HashMap;
+
+use crate::affine_cipher::{Affine, AffineKey};
+use crate::alphabet::ASCII_LETTERS;
+use crate::atbash_cipher::Atbash;
+use crate::autokey_cipher::{Autokey, Feedback};
+use crate::caeser_cipher::Caesar;
+use crate::cipher::DynCipher;
+use crate::columnar_transposition::{Columnar, DoubleTransposition};
+use crate::error::{Error, Result};
+use crate::gronsfeld_cipher::Gronsfeld;
+use crate::hill_cipher::{Hill, Matrix};
+use crate::one_time_pad::OneTimePad;
+use crate::playfair_cipher::Playfair;
+use crate::porta_cipher::Porta;
+use crate::rail_fence_cipher::{RailFence, RailFenceKey};
+use crate::rot13::Rot13;
+use crate::running_key_cipher::RunningKey;
+use crate::substitution_cipher::Substitution;
+use crate::trithemius_cipher::{Trithemius, TrithemiusKey};
+use crate::vigenere_cipher::{Mode, Vigenere};
+
+type Factory = Box<dyn Fn(&str) -> Result<Box<dyn DynCipher>> + Send + Sync>;
+
+/// Maps cipher names to factories that build a boxed cipher from a string key,
+/// so tools can pick the cipher from configuration instead of a hand written `match`.
+pub struct CipherRegistry {
+    factories: HashMap<String, Factory>,
+}
+
+impl Default for CipherRegistry {
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        registry.register("caesar", |key| {
+            let shift: u8 = key.trim().parse().
+                map_err(|_| Error::InvalidKey(format!("caesar key must be a shift between 0 and 255, got {:?}", key)))?;
+            Ok(Box::new(Caesar::new(shift)?))
+        });
+        registry.register("affine", |key| {
+            let invalid = || Error::InvalidKey(format!("affine key must be two numbers \"a,b\", got {:?}", key));
+            let (a, b) = key.split_once(',').ok_or_else(invalid)?;
+            let a = a.trim().parse().map_err(|_| invalid())?;
+            let b = b.trim().parse().map_err(|_| invalid())?;
+            Ok(Box::new(Affine::new(AffineKey::new(a, b))?))
+        });
+        registry.register("columnar", |key| Ok(Box::new(Columnar::new(key)?)));
+        registry.register("double_transposition", |key| {
+            let (first, second) = key.split_once(',').ok_or_else(|| Error::InvalidKey(
+                format!("double transposition key must be two keywords \"first,second\", got {:?}", key)
+            ))?;
+            Ok(Box::new(DoubleTransposition::new(first, second)?))
+        });
+        registry.register("gronsfeld", |key| Ok(Box::new(Gronsfeld::new(key)?)));
+        registry.register("trithemius", |key| {
+            // empty for the classic tableau, or "start,step"
+            if key.trim().is_empty() {
+                return Ok(Box::new(Trithemius::new(TrithemiusKey::classic())));
+            }
+            let invalid = || Error::InvalidKey(format!("trithemius key must be empty or \"start,step\", got {:?}", key));
+            let (start, step) = key.split_once(',').ok_or_else(invalid)?;
+            let start = start.trim().parse().map_err(|_| invalid())?;
+            let step = step.trim().parse().map_err(|_| invalid())?;
+            Ok(Box::new(Trithemius::new(TrithemiusKey::new(start, step))))
+        });
+        registry.register("hill", |key| {
+            // a keyword with a square number of letters, or the entries row by row: "3,3,2,5"
+            let matrix = match key.contains(',') {
+                true => {
+                    let entries: Vec<usize> = key.split(',').map(|x| x.trim().parse()).collect::<std::result::Result<_, _>>().
+                        map_err(|_| Error::InvalidKey(format!("hill key must be a keyword or comma separated numbers, got {:?}", key)))?;
+                    Matrix::from_entries(&entries)?
+                }
+                false => Matrix::from_keyword(key, &ASCII_LETTERS)?,
+            };
+            Ok(Box::new(Hill::new(matrix)?))
+        });
+        registry.register("vigenere", |key| Ok(Box::new(Vigenere::new(key)?)));
+        registry.register("autokey", |key| Ok(Box::new(Autokey::new(key)?)));
+        registry.register("ciphertext_autokey", |key| Ok(Box::new(Autokey::new(key)?.with_feedback(Feedback::Ciphertext))));
+        registry.register("beaufort", |key| Ok(Box::new(Vigenere::new(key)?.with_mode(Mode::Beaufort))));
+        registry.register("variant_beaufort", |key| Ok(Box::new(Vigenere::new(key)?.with_mode(Mode::VariantBeaufort))));
+        registry.register("atbash", |key| {
+            no_key("atbash", key)?;
+            Ok(Box::new(Atbash::new()))
+        });
+        registry.register("rot13", |key| {
+            no_key("rot13", key)?;
+            Ok(Box::new(Rot13::new()))
+        });
+        registry.register("otp", |key| Ok(Box::new(OneTimePad::new(key.as_bytes())?)));
+        registry.register("playfair", |key| Ok(Box::new(Playfair::new(key))));
+        registry.register("porta", |key| Ok(Box::new(Porta::new(key)?)));
+        registry.register("railfence", |key| {
+            // "rails" or "rails,offset"
+            let invalid = || Error::InvalidKey(format!("rail fence key must be \"rails\" or \"rails,offset\", got {:?}", key));
+            let (rails, offset) = key.split_once(',').unwrap_or((key, "0"));
+            let rails = rails.trim().parse().map_err(|_| invalid())?;
+            let offset = offset.trim().parse().map_err(|_| invalid())?;
+            Ok(Box::new(RailFence::new(RailFenceKey::new(rails, offset))?))
+        });
+        // the key is the book text itself, used from its first letter
+        registry.register("running_key", |key| Ok(Box::new(RunningKey::new(key, 0)?)));
+        registry.register("substitution", |key| Ok(Box::new(Substitution::new(key)?)));
+        registry
+    }
+}
+
+impl CipherRegistry {
+    /// Registry pre-populated with every cipher in the kit.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registry without any ciphers registered.
+    pub fn empty() -> Self {
+        Self { factories: HashMap::new() }
+    }
+
+    /// Registers (or replaces) a cipher under `name`. Names are case insensitive.
+    pub fn register<F>(&mut self, name: &str, factory: F)
+        where
+            F: Fn(&str) -> Result<Box<dyn DynCipher>> + Send + Sync + 'static,
+    {
+        self.factories.insert(name.to_lowercase(), Box::new(factory));
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.factories.contains_key(&name.to_lowercase())
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.factories.keys().map(|name| name.as_str()).collect();
+        names.sort();
+        names
+    }
+
+    pub fn build(&self, name: &str, key: &str) -> Result<Box<dyn DynCipher>> {
+        let factory = self.factories.get(&name.to_lowercase()).
+            ok_or_else(|| Error::UnknownCipher {
+                name: name.to_string(),
+                known: self.names().into_iter().map(String::from).collect(),
+            })?;
+        factory(key)
+    }
+}
+
+fn no_key(name: &str, key: &str) -> Result<()> {
+    if !key.trim().is_empty() {
+        return Err(Error::InvalidKey(format!("{} does not take a key, got {:?}", name, key)));
+    }
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_build() {
+        let registry = CipherRegistry::new();
+        assert_eq!(vec!["affine", "atbash", "autokey", "beaufort", "caesar", "ciphertext_autokey", "columnar", "double_transposition", "gronsfeld", "hill", "otp", "playfair", "porta", "railfence", "rot13", "running_key", "substitution", "trithemius", "variant_beaufort", "vigenere"], registry.names());
+
+        let caesar = registry.build("Caesar", "6").expect("valid caesar");
+        assert_eq!("gggg26QQGHpgnyfd", caesar.encrypt("aaaa26KKABjahszx").expect("encrypt"));
+
+        let vigenere = registry.build("vigenere", "randalthor").expect("valid vigenere");
+        assert_eq!("Be normal, and the crowd", vigenere.decrypt("Se arrxts, oeu tuh cchdr").expect("decrypt"));
+
+        let beaufort = registry.build("beaufort", "fortification").expect("valid beaufort");
+        assert_eq!("CKMPVCPVWPIWUJOGIUAPVWRIWUUK", beaufort.encrypt("DEFENDTHEEASTWALLOFTHECASTLE").expect("encrypt"));
+
+        let autokey = registry.build("autokey", "queenly").expect("valid autokey");
+        assert_eq!("qnxepv yt wtwp", autokey.encrypt("attack at dawn").expect("encrypt"));
+
+        let gronsfeld = registry.build("gronsfeld", "31415").expect("valid gronsfeld");
+        assert_eq!("Duxbhn, Dlpxho!", gronsfeld.encrypt("Attack, Chosen!").expect("encrypt"));
+
+        let trithemius = registry.build("trithemius", "").expect("valid trithemius");
+        assert_eq!("Tig tynir", trithemius.encrypt("The quick").expect("encrypt"));
+        let trithemius = registry.build("trithemius", "3, 3").expect("valid trithemius");
+        assert_eq!("dgjmp", trithemius.encrypt("aaaaa").expect("encrypt"));
+
+        let porta = registry.build("porta", "fortification").expect("valid porta");
+        assert_eq!("SYNNJSCVRNRLAHUTUKUCVRYRLANY", porta.encrypt("DEFENDTHEEASTWALLOFTHECASTLE").expect("encrypt"));
+
+        let rot13 = registry.build("rot13", "").expect("valid rot13");
+        assert_eq!("nnnn", rot13.encrypt("aaaa").expect("encrypt"));
+
+        let affine = registry.build("affine", "5, 8").expect("valid affine");
+        assert_eq!("Ihhwvc Swfrcp", affine.encrypt("Affine Cipher").expect("encrypt"));
+
+        let rail_fence = registry.build("railfence", "3").expect("valid rail fence");
+        assert_eq!("WECRLTEERDSOEEFEAOCAIVDEN", rail_fence.encrypt("WEAREDISCOVEREDFLEEATONCE").expect("encrypt"));
+
+        let hill = registry.build("hill", "3, 3, 2, 5").expect("valid hill");
+        assert_eq!("Hiat ws, dsf!d", hill.encrypt("Help me, now!").expect("encrypt"));
+        assert_eq!(registry.build("hill", "GYBNQKURP").expect("valid hill").encrypt("ACT").expect("encrypt"), "POH");
+
+        let otp = registry.build("otp", "secret key").expect("valid otp");
+        let encrypted = otp.encrypt("hello").expect("encrypt");
+        assert_eq!("hello", otp.decrypt(&encrypted).expect("decrypt"));
+    }
+
+    #[test]
+    fn test_errors() {
+        let registry = CipherRegistry::new();
+        let err = registry.build("enigma", "").err().expect("unknown cipher");
+        assert!(matches!(err, Error::UnknownCipher { .. }));
+        assert_eq!("unknown cipher \"enigma\", expected one of: affine, atbash, autokey, beaufort, caesar, ciphertext_autokey, columnar, double_transposition, gronsfeld, hill, otp, playfair, porta, railfence, rot13, running_key, substitution, trithemius, variant_beaufort, vigenere", err.to_string());
+
+        let err = registry.build("caesar", "three").err().expect("malformed key");
+        assert_eq!("invalid key: caesar key must be a shift between 0 and 255, got \"three\"", err.to_string());
+
+        assert!(matches!(registry.build("atbash", "key"), Err(Error::InvalidKey(_))));
+        assert!(matches!(registry.build("affine", "5"), Err(Error::InvalidKey(_))));
+        assert!(matches!(registry.build("affine", "13,2"), Err(Error::InvalidKey(_))));
+        let affine = registry.build("affine", "18446744073709551615,0").expect("coprime to 26");
+        assert_eq!("Axxqni Eqrbiv", affine.encrypt("Affine Cipher").expect("encrypt"));
+        assert!(matches!(registry.build("hill", "2,4,1,3"), Err(Error::InvalidKey(_))));
+        assert!(matches!(registry.build("hill", "1,2,3"), Err(Error::InvalidKey(_))));
+        assert!(matches!(registry.build("railfence", "18446744073709551615"), Err(Error::InvalidKey(_))));
+        let hill = registry.build("hill", "18446744073709551615,0,0,1").expect("invertible mod 26");
+        assert_eq!("Bejp", hill.encrypt("Help").expect("encrypt"));
+        assert!(matches!(registry.build("running_key", "..."), Err(Error::InvalidKey(_))));
+        assert!(matches!(registry.build("gronsfeld", "pi"), Err(Error::InvalidKey(_))));
+        assert!(matches!(registry.build("trithemius", "3"), Err(Error::InvalidKey(_))));
+        assert!(matches!(registry.build("vigenere", ""), Err(Error::InvalidKey(_))));
+    }
+
+    #[test]
+    fn test_register_third_party() {
+        let mut registry = CipherRegistry::empty();
+        assert!(!registry.contains("rot13"));
+        registry.register("shout", |key| {
+            no_key("shout", key)?;
+            Ok(Box::new(Shout))
+        });
+        let shout = registry.build("SHOUT", "").expect("registered");
+        assert_eq!("HELLO", shout.encrypt("hello").expect("encrypt"));
+    }
+
+    struct Shout;
+
+    impl crate::cipher::Cipher for Shout {
+        type Key = ();
+
+        fn validate_key(_key: &()) -> Result<()> {
+            Ok(())
+        }
+
+        fn key(&self) -> &() {
+            &()
+        }
+
+        fn encrypt(&self, message: &str) -> Result<String> {
+            Ok(message.to_uppercase())
+        }
+
+        fn decrypt(&self, message: &str) -> Result<String> {
+            Ok(message.to_lowercase())
+        }
+    }
+}
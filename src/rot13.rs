@@ -1,3 +1,12 @@
+use rand::{CryptoRng, RngCore};
+
+use crate::alphabet::{Alphabet, ForeignCharPolicy};
+use crate::caeser_cipher::CaesarStream;
+use crate::cipher::Cipher;
+use crate::error::Result;
+use crate::keygen::GenerateKey;
+use crate::stream::{StreamingCipher, TextTransform};
+
 pub fn encode(message: &str) -> String { crate::caeser_cipher::encode(message, 13) }
 
 
@@ -5,6 +14,71 @@ pub fn decode(message: &str) -> String {
     crate::caeser_cipher::decode(message, 13)
 }
 
+// rotates by half the alphabet, which is its own inverse for alphabets of even length
+pub fn encode_with(message: &str, alphabet: &Alphabet) -> String {
+    crate::caeser_cipher::encode_with(message, alphabet.len() / 2, alphabet)
+}
+
+
+pub fn decode_with(message: &str, alphabet: &Alphabet) -> String {
+    crate::caeser_cipher::decode_with(message, alphabet.len() / 2, alphabet)
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Rot13 {
+    key: (),
+    alphabet: Alphabet,
+}
+
+impl Rot13 {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_alphabet(alphabet: Alphabet) -> Self {
+        Self { key: (), alphabet }
+    }
+}
+
+impl Cipher for Rot13 {
+    type Key = ();
+
+    fn validate_key(_key: &()) -> Result<()> {
+        Ok(())
+    }
+
+    fn key(&self) -> &() {
+        &self.key
+    }
+
+    fn encrypt(&self, message: &str) -> Result<String> {
+        Ok(encode_with(message, &self.alphabet))
+    }
+
+    fn decrypt(&self, message: &str) -> Result<String> {
+        Ok(decode_with(message, &self.alphabet))
+    }
+}
+
+impl StreamingCipher for Rot13 {
+    type Encryptor = TextTransform<CaesarStream>;
+    type Decryptor = TextTransform<CaesarStream>;
+
+    fn encryptor(&self) -> Self::Encryptor {
+        let key = self.alphabet.len() / 2;
+        TextTransform::new(CaesarStream::new(key, self.alphabet.clone(), ForeignCharPolicy::PassThrough, true))
+    }
+
+    fn decryptor(&self) -> Self::Decryptor {
+        let key = self.alphabet.len() / 2;
+        TextTransform::new(CaesarStream::new(key, self.alphabet.clone(), ForeignCharPolicy::PassThrough, false))
+    }
+}
+
+impl GenerateKey for Rot13 {
+    fn generate_key<R: RngCore + CryptoRng + ?Sized>(_rng: &mut R, _length: usize) {}
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -22,6 +96,22 @@ mod test {
         assert_eq!("aaaa", &decode("nnnn"));
         assert_eq!("aaaa26KKABjahszx", &decode("nnnn26XXNOwnufmk"));
     }
-}
 
+    #[test]
+    fn test_alphabets() {
+        let greek = Alphabet::greek();
+        assert_eq!("Νξο", encode_with("Αβγ", &greek));
+        assert_eq!("Αβγ", encode_with("Νξο", &greek));
 
+        let printable = Alphabet::printable_ascii();
+        let encoded = encode_with("Hello, World!", &printable);
+        assert_eq!("Hello, World!", decode_with(&encoded, &printable));
+    }
+
+    #[test]
+    fn test_cipher_trait() {
+        let cipher = Rot13::new();
+        assert_eq!("nnnn26XXNOwnufmk", cipher.encrypt("aaaa26KKABjahszx").expect("encrypt"));
+        assert_eq!("aaaa26KKABjahszx", cipher.decrypt("nnnn26XXNOwnufmk").expect("decrypt"));
+    }
+}
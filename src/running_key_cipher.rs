@@ -0,0 +1,310 @@
+use std::io;
+use std::path::Path;
+
+use crate::alphabet::{Alphabet, ASCII_LETTERS};
+use crate::cipher::Cipher;
+use crate::detection::detect_english::{SHORT_WORDS, TRIE_DB};
+use crate::detection::ngram::NgramScorer;
+use crate::detection::trie::Trie;
+use crate::error::{Error, Result};
+use crate::stream::{CharTransform, StreamingCipher, TextTransform};
+
+/// Frequent English words, dragged across the ciphertext by [`crib_drag`].
+pub const COMMON_CRIBS: &[&str] = &[
+    "the", "and", "that", "have", "with", "this", "from", "they", "were", "which", "there", "their", "would",
+    "about", "because", "through",
+];
+
+pub fn encode(message: &str, key_text: &str, offset: usize) -> Result<String> {
+    RunningKey::new(key_text, offset)?.encrypt(message)
+}
+
+
+pub fn decode(message: &str, key_text: &str, offset: usize) -> Result<String> {
+    RunningKey::new(key_text, offset)?.decrypt(message)
+}
+
+
+/// Running key cipher: Vigenere with a key as long as the message, read from
+/// a book text starting `offset` letters in. Only the letters of the key text
+/// count, for the offset as well as for the key.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunningKey {
+    key_text: String,
+    offset: usize,
+    alphabet: Alphabet,
+}
+
+impl RunningKey {
+    pub fn new(key_text: &str, offset: usize) -> Result<Self> {
+        Self::with_alphabet(key_text, offset, ASCII_LETTERS.clone())
+    }
+
+    /// Reads the key text from the file at `path`.
+    pub fn from_file<P: AsRef<Path>>(path: P, offset: usize) -> Result<Self> {
+        let key_text = std::fs::read_to_string(path)?;
+        Self::new(&key_text, offset)
+    }
+
+    pub fn with_alphabet(key_text: &str, offset: usize, alphabet: Alphabet) -> Result<Self> {
+        let letters = alphabet.indices(key_text).len();
+        if offset >= letters {
+            return Err(Error::InvalidKey(format!(
+                "running key offset {} is past the end of the key text, which has {} letters", offset, letters
+            )));
+        }
+        Ok(Self { key_text: key_text.to_string(), offset, alphabet })
+    }
+
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    fn handle(&self, message: &str, encode: bool) -> Result<String> {
+        let key: Vec<usize> = self.alphabet.indices(&self.key_text).into_iter().skip(self.offset).collect();
+        let needed = self.alphabet.indices(message).len();
+        if needed > key.len() {
+            return Err(Error::InvalidKey(format!(
+                "running key has {} letters after offset {} but the message has {}", key.len(), self.offset, needed
+            )));
+        }
+        let mut key = key.into_iter();
+        Ok(message.chars().
+            map(|ch| match self.alphabet.contains(ch) {
+                true => self.alphabet.shift(ch, key.next().expect("checked above"), encode).unwrap_or(ch),
+                false => ch,
+            }).
+            collect())
+    }
+}
+
+impl Cipher for RunningKey {
+    type Key = String;
+
+    fn validate_key(key: &String) -> Result<()> {
+        if !key.chars().any(|ch| ch.is_ascii_alphabetic()) {
+            return Err(Error::InvalidKey("running key text must contain at least one ascii letter".to_string()));
+        }
+        Ok(())
+    }
+
+    fn key(&self) -> &String {
+        &self.key_text
+    }
+
+    fn encrypt(&self, message: &str) -> Result<String> {
+        self.handle(message, true)
+    }
+
+    fn decrypt(&self, message: &str) -> Result<String> {
+        self.handle(message, false)
+    }
+}
+
+impl StreamingCipher for RunningKey {
+    type Encryptor = TextTransform<RunningKeyStream>;
+    type Decryptor = TextTransform<RunningKeyStream>;
+
+    fn encryptor(&self) -> Self::Encryptor {
+        TextTransform::new(RunningKeyStream::new(self, true))
+    }
+
+    fn decryptor(&self) -> Self::Decryptor {
+        TextTransform::new(RunningKeyStream::new(self, false))
+    }
+}
+
+/// Character by character form of the running key cipher that remembers its
+/// position in the key text, used by the streaming adapters. Fails at the
+/// first letter past the end of the key text.
+pub struct RunningKeyStream {
+    key: Vec<usize>,
+    offset: usize,
+    next_key: usize,
+    alphabet: Alphabet,
+    encode: bool,
+}
+
+impl RunningKeyStream {
+    fn new(cipher: &RunningKey, encode: bool) -> Self {
+        let key = cipher.alphabet.indices(&cipher.key_text).into_iter().skip(cipher.offset).collect();
+        Self { key, offset: cipher.offset, next_key: 0, alphabet: cipher.alphabet.clone(), encode }
+    }
+}
+
+impl CharTransform for RunningKeyStream {
+    fn transform(&mut self, ch: char, output: &mut String) -> io::Result<()> {
+        if !self.alphabet.contains(ch) {
+            output.push(ch);
+            return Ok(());
+        }
+        let Some(&shift) = self.key.get(self.next_key) else {
+            return Err(Error::InvalidKey(format!(
+                "running key has {} letters after offset {} but the message has more", self.key.len(), self.offset
+            )).into());
+        };
+        output.push(self.alphabet.shift(ch, shift, self.encode).unwrap_or(ch));
+        self.next_key += 1;
+        Ok(())
+    }
+}
+
+/// A crib that fits the ciphertext at `position` (counted in letters), with
+/// the text it implies on the other side. The cipher is symmetric in
+/// plaintext and key, so the two may be swapped.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fragment {
+    pub position: usize,
+    pub plaintext: String,
+    pub key: String,
+    /// Trigram fitness per trigram of the implied text.
+    pub score: f64,
+}
+
+/// Drags [`COMMON_CRIBS`] across the ciphertext with the bundled dictionary,
+/// see [`CribDragger::drag`].
+pub fn crib_drag(message: &str) -> Result<Vec<Fragment>> {
+    Ok(CribDragger::new_with_fix_db()?.drag(message, COMMON_CRIBS))
+}
+
+/// Crib dragging attack on the running key cipher. Both plaintext and key are
+/// English, so wherever a guessed word sits in one of them, the letters it
+/// implies in the other must read as English too.
+pub struct CribDragger {
+    words: Trie,
+    // every word spelled backwards, to recognise word endings
+    endings: Trie,
+    // fragments are short, trigrams suit them better than quadgrams
+    scorer: &'static NgramScorer,
+}
+
+impl CribDragger {
+    pub fn new(words: Trie) -> Result<Self> {
+        let endings = words.reversed();
+        Ok(Self { words, endings, scorer: NgramScorer::english(3)? })
+    }
+
+    pub fn new_with_fix_db() -> Result<Self> {
+        Self::new(Trie::from_json_file(TRIE_DB)?)
+    }
+
+    /// Tries every crib at every letter position of `message` and keeps the
+    /// positions where the implied text is plausible English with at least
+    /// half of it in whole words, ranked by the trigram fitness of the
+    /// implied text.
+    pub fn drag(&self, message: &str, cribs: &[&str]) -> Vec<Fragment> {
+        let letters = ASCII_LETTERS.indices(message);
+        let mut fragments = Vec::new();
+        for crib in cribs {
+            let crib_letters = ASCII_LETTERS.indices(crib);
+            if crib_letters.is_empty() || crib_letters.len() > letters.len() {
+                continue;
+            }
+            let crib: String = crib_letters.iter().map(|&x| ASCII_LETTERS.symbols()[x]).collect();
+            for position in 0..=letters.len() - crib_letters.len() {
+                let implied: String = crib_letters.iter().zip(&letters[position..]).
+                    map(|(p, c)| ASCII_LETTERS.symbols()[(c + 26 - p) % 26]).
+                    collect();
+                if self.plausibility(&implied).is_some_and(|coverage| coverage >= 0.5) {
+                    let score = self.scorer.score_per_ngram(&implied);
+                    fragments.push(Fragment { position, plaintext: crib.clone(), key: implied, score });
+                }
+            }
+        }
+        fragments.sort_by(|a, b| b.score.total_cmp(&a.score));
+        fragments
+    }
+
+    /// Whether `text` can be a piece of English with the spaces removed: the
+    /// end of a word, whole words, then the start of a word. Returns the
+    /// largest share of its letters in whole words, `None` if it can't.
+    pub fn plausibility(&self, text: &str) -> Option<f64> {
+        let chars: Vec<char> = text.chars().map(|ch| ch.to_ascii_lowercase()).collect();
+        if chars.is_empty() {
+            return None;
+        }
+        // covered[i] is the most letters of chars[i..] in whole words, followed by the start of a word
+        let mut covered: Vec<Option<usize>> = vec![None; chars.len() + 1];
+        covered[chars.len()] = Some(0);
+        for i in (0..chars.len()).rev() {
+            if self.words.contains_prefix(chars[i..].iter().copied()) {
+                covered[i] = Some(0);
+            }
+            for length in self.words.prefix_lengths(chars[i..].iter().copied()) {
+                if length < 3 && !SHORT_WORDS.contains(&chars[i..i + length].iter().collect::<String>().as_str()) {
+                    continue;
+                }
+                if let Some(after) = covered[i + length] {
+                    covered[i] = covered[i].max(Some(after + length));
+                }
+            }
+        }
+        let mut best = covered[0];
+        for i in 0..chars.len() {
+            // the letters before i + 1 read backwards must start a reversed word
+            if !self.endings.contains_prefix(chars[..=i].iter().rev().copied()) {
+                continue;
+            }
+            best = best.max(covered[i + 1]);
+        }
+        best.map(|letters| letters as f64 / chars.len() as f64)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const BOOK: &str = "It was the best of times, it was the worst of times, it was the age of wisdom, it was the age of
+        foolishness, it was the epoch of belief, it was the epoch of incredulity, it was the season of Light, it was the
+        season of Darkness, it was the spring of hope, it was the winter of despair.";
+
+    #[test]
+    fn test_encode() {
+        let encoded = encode("Meet me at the usual place", BOOK, 0).expect("long enough");
+        assert_eq!(encode("Meet me at the usual place", "itwasthebestoftimesitwasthe", 0).expect("long enough"), encoded);
+        assert_eq!("Meet me at the usual place", decode(&encoded, BOOK, 0).expect("long enough"));
+
+        let encoded = encode("Meet me at the usual place", BOOK, 12).expect("long enough");
+        assert_eq!(encode("Meet me at the usual place", "of times it was the worst of", 0).expect("long enough"), encoded);
+        assert_eq!("Meet me at the usual place", decode(&encoded, BOOK, 12).expect("long enough"));
+
+        assert!(matches!(encode("far too long for the key", "short", 0), Err(Error::InvalidKey(_))));
+        assert!(matches!(RunningKey::new("short", 5), Err(Error::InvalidKey(_))));
+    }
+
+    #[test]
+    fn test_from_file() {
+        let path = std::env::temp_dir().join(format!("rusty_cryptography_kit_running_key_{}.txt", std::process::id()));
+        std::fs::write(&path, BOOK).expect("write book");
+        let cipher = RunningKey::from_file(&path, 3);
+        // removed before any assertion can fail
+        std::fs::remove_file(&path).expect("remove book");
+        let cipher = cipher.expect("book exists");
+        assert_eq!(3, cipher.offset());
+        assert_eq!(encode("attack at dawn", BOOK, 3).expect("long enough"), cipher.encrypt("attack at dawn").expect("encrypt"));
+
+        assert!(matches!(RunningKey::from_file(&path, 0), Err(Error::Io(_))));
+    }
+
+    #[test]
+    fn test_crib_drag() {
+        let dragger = CribDragger::new_with_fix_db().expect("dictionary");
+        assert_eq!(Some(1.0), dragger.plausibility("thebest"));
+        // end of "was", whole "the", start of "season"
+        assert!(dragger.plausibility("asthesea").is_some());
+        assert_eq!(None, dragger.plausibility("xqzjvk"));
+
+        let message = "The treasure is buried under the old oak tree by the river";
+        let encoded = encode(message, BOOK, 40).expect("long enough");
+        let fragments = dragger.drag(&encoded, COMMON_CRIBS);
+        let letters: String = ASCII_LETTERS.indices(BOOK).iter().skip(40).map(|&x| ASCII_LETTERS.symbols()[x]).collect();
+        // "the" is the plaintext at these letters, so the key fragments there are the book text
+        for position in [0, 24, 39] {
+            let found = fragments.iter().find(|fragment| fragment.position == position && fragment.plaintext == "the").expect("crib fits");
+            assert_eq!(letters[position..position + 3], found.key);
+        }
+        assert!(fragments.windows(2).all(|pair| pair[0].score >= pair[1].score));
+    }
+}
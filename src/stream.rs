@@ -0,0 +1,381 @@
+//! Incremental encryption of readers and writers. Every cipher that works one
+//! character (or byte) at a time implements [`StreamingCipher`]. The
+//! transposition and block ciphers (rail fence, columnar, Playfair and Hill)
+//! do not: their output depends on the whole message or on padding its end.
+
+use std::io::{self, Read, Write};
+
+const BUFFER_SIZE: usize = 8 * 1024;
+
+/// A stateful cipher applied to a byte stream one chunk at a time. State such
+/// as the Vigenere key position or the pad offset is carried across chunks, so
+/// the output is the same however the input happens to be split.
+pub trait Transform {
+    /// Appends the transformed `input` to `output` and returns how many bytes
+    /// of `input` were used. When part of the input fails, the part before it
+    /// is still transformed and counted, and the error is returned once it is
+    /// the first thing left to transform.
+    fn update(&mut self, input: &[u8], output: &mut Vec<u8>) -> io::Result<usize>;
+
+    /// Called once at the end of the stream to flush anything still buffered.
+    fn finish(&mut self, _output: &mut Vec<u8>) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A stateful text cipher working one character at a time.
+pub trait CharTransform {
+    fn transform(&mut self, ch: char, output: &mut String) -> io::Result<()>;
+}
+
+/// Runs a [`CharTransform`] over UTF-8 encoded bytes. Characters split across
+/// chunk boundaries are held back until complete; bytes that are not valid
+/// UTF-8 are passed through untouched.
+pub struct TextTransform<C: CharTransform> {
+    chars: C,
+    pending: Vec<u8>,
+    text: String,
+}
+
+impl<C: CharTransform> TextTransform<C> {
+    pub fn new(chars: C) -> Self {
+        Self { chars, pending: Vec::new(), text: String::new() }
+    }
+
+    // on failure the characters before the failing one are still output, and
+    // the error comes with their length in bytes
+    fn push_text(&mut self, text: &str, output: &mut Vec<u8>) -> Result<(), (usize, io::Error)> {
+        self.text.clear();
+        let mut result = Ok(());
+        for (i, ch) in text.char_indices() {
+            if let Err(e) = self.chars.transform(ch, &mut self.text) {
+                result = Err((i, e));
+                break;
+            }
+        }
+        output.extend_from_slice(self.text.as_bytes());
+        result
+    }
+}
+
+impl<C: CharTransform> Transform for TextTransform<C> {
+    fn update(&mut self, input: &[u8], output: &mut Vec<u8>) -> io::Result<usize> {
+        // bytes of an incomplete character held back from the previous chunk
+        let held = self.pending.len();
+        let mut data = std::mem::take(&mut self.pending);
+        data.extend_from_slice(input);
+
+        let mut offset = 0;
+        while offset < data.len() {
+            let rest = &data[offset..];
+            let (valid, invalid) = match std::str::from_utf8(rest) {
+                Ok(text) => (text, None),
+                Err(e) => (std::str::from_utf8(&rest[..e.valid_up_to()]).expect("validated above"), Some(e.error_len())),
+            };
+            if let Err((failed, e)) = self.push_text(valid, output) {
+                let failed = offset + failed;
+                if failed <= held {
+                    // nothing of `input` got through, keep the state as it was
+                    self.pending = data[..held].to_vec();
+                    return Err(e);
+                }
+                return Ok(failed - held);
+            }
+            offset += valid.len();
+            match invalid {
+                None => break,
+                Some(Some(invalid)) => {
+                    output.extend_from_slice(&data[offset..offset + invalid]);
+                    offset += invalid;
+                }
+                // the chunk ends in the middle of a character
+                Some(None) => {
+                    self.pending = data[offset..].to_vec();
+                    break;
+                }
+            }
+        }
+        Ok(input.len())
+    }
+
+    fn finish(&mut self, output: &mut Vec<u8>) -> io::Result<()> {
+        // a truncated character at the very end of the stream can never be completed
+        output.append(&mut self.pending);
+        Ok(())
+    }
+}
+
+/// Ciphers that can process data incrementally through [`CipherWriter`] and
+/// [`CipherReader`] in constant memory.
+pub trait StreamingCipher {
+    type Encryptor: Transform;
+    type Decryptor: Transform;
+
+    fn encryptor(&self) -> Self::Encryptor;
+
+    fn decryptor(&self) -> Self::Decryptor;
+
+    /// Encrypts everything written before passing it on to `inner`.
+    fn encrypting_writer<W: Write>(&self, inner: W) -> CipherWriter<W, Self::Encryptor> {
+        CipherWriter::new(inner, self.encryptor())
+    }
+
+    /// Decrypts everything written before passing it on to `inner`.
+    fn decrypting_writer<W: Write>(&self, inner: W) -> CipherWriter<W, Self::Decryptor> {
+        CipherWriter::new(inner, self.decryptor())
+    }
+
+    /// Encrypts everything read from `inner`.
+    fn encrypting_reader<R: Read>(&self, inner: R) -> CipherReader<R, Self::Encryptor> {
+        CipherReader::new(inner, self.encryptor())
+    }
+
+    /// Decrypts everything read from `inner`.
+    fn decrypting_reader<R: Read>(&self, inner: R) -> CipherReader<R, Self::Decryptor> {
+        CipherReader::new(inner, self.decryptor())
+    }
+}
+
+/// Wraps a writer, transforming every chunk before writing it through. Call
+/// [`CipherWriter::finish`] at the end of the stream; dropping the writer
+/// finishes it too but ignores any error.
+pub struct CipherWriter<W: Write, T: Transform> {
+    inner: Option<W>,
+    transform: T,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write, T: Transform> CipherWriter<W, T> {
+    pub fn new(inner: W, transform: T) -> Self {
+        Self { inner: Some(inner), transform, buffer: Vec::with_capacity(BUFFER_SIZE) }
+    }
+
+    /// Flushes the end of the stream and hands back the inner writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.finish_stream()?;
+        Ok(self.inner.take().expect("inner writer is only taken once"))
+    }
+
+    fn finish_stream(&mut self) -> io::Result<()> {
+        self.buffer.clear();
+        self.transform.finish(&mut self.buffer)?;
+        if let Some(inner) = self.inner.as_mut() {
+            inner.write_all(&self.buffer)?;
+            inner.flush()?;
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write, T: Transform> Write for CipherWriter<W, T> {
+    // a chunk that fails part way writes out the part before the failure and reports it as a short
+    // write, the error comes with the next call
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.clear();
+        let written = self.transform.update(buf, &mut self.buffer)?;
+        self.inner.as_mut().expect("writer used after finish").write_all(&self.buffer)?;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.as_mut().expect("writer used after finish").flush()
+    }
+}
+
+impl<W: Write, T: Transform> Drop for CipherWriter<W, T> {
+    fn drop(&mut self) {
+        if self.inner.is_some() {
+            let _ = self.finish_stream();
+        }
+    }
+}
+
+/// Wraps a reader, transforming everything read from it.
+pub struct CipherReader<R: Read, T: Transform> {
+    inner: R,
+    transform: T,
+    input: Vec<u8>,
+    // input[consumed..filled] is read but not yet transformed
+    consumed: usize,
+    filled: usize,
+    output: Vec<u8>,
+    position: usize,
+    eof: bool,
+}
+
+impl<R: Read, T: Transform> CipherReader<R, T> {
+    pub fn new(inner: R, transform: T) -> Self {
+        Self {
+            inner,
+            transform,
+            input: vec![0; BUFFER_SIZE],
+            consumed: 0,
+            filled: 0,
+            output: Vec::with_capacity(BUFFER_SIZE),
+            position: 0,
+            eof: false,
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read, T: Transform> Read for CipherReader<R, T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // a chunk can transform to nothing (e.g. half of a character) so keep reading until there is output
+        while self.position == self.output.len() && !self.eof {
+            self.output.clear();
+            self.position = 0;
+            if self.consumed == self.filled {
+                self.filled = self.inner.read(&mut self.input)?;
+                self.consumed = 0;
+                if self.filled == 0 {
+                    self.transform.finish(&mut self.output)?;
+                    self.eof = true;
+                    continue;
+                }
+            }
+            // what a failing chunk transformed before the failure is returned first
+            self.consumed += self.transform.update(&self.input[self.consumed..self.filled], &mut self.output)?;
+        }
+
+        let n = buf.len().min(self.output.len() - self.position);
+        buf[..n].copy_from_slice(&self.output[self.position..self.position + n]);
+        self.position += n;
+        Ok(n)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::affine_cipher::{Affine, AffineKey};
+    use crate::alphabet::Alphabet;
+    use crate::atbash_cipher::Atbash;
+    use crate::autokey_cipher::{Autokey, Feedback};
+    use crate::caeser_cipher::Caesar;
+    use crate::cipher::Cipher;
+    use crate::gronsfeld_cipher::Gronsfeld;
+    use crate::one_time_pad::OneTimePad;
+    use crate::porta_cipher::Porta;
+    use crate::rot13::Rot13;
+    use crate::running_key_cipher::RunningKey;
+    use crate::substitution_cipher::Substitution;
+    use crate::trithemius_cipher::{Trithemius, TrithemiusKey};
+    use crate::vigenere_cipher::{Mode, Vigenere};
+
+    const TEXT: &str = "Be normal, and the crowd will accept you. Be deranged, and they will make you their leader. Straße, Привет 🦀!";
+
+    // writes `data` in chunks of `chunk` bytes, splitting multi byte characters
+    fn write_chunked<W: Write>(mut writer: W, data: &[u8], chunk: usize) -> W {
+        for part in data.chunks(chunk) {
+            writer.write_all(part).expect("write");
+        }
+        writer
+    }
+
+    fn check<C: StreamingCipher + Cipher>(cipher: &C) {
+        let expected = cipher.encrypt(TEXT).expect("encrypt");
+        for chunk in [1, 2, 3, 7, 1024] {
+            let writer = write_chunked(cipher.encrypting_writer(Vec::new()), TEXT.as_bytes(), chunk);
+            let encrypted = writer.finish().expect("finish");
+            assert_eq!(expected.as_bytes(), &encrypted[..], "chunk size {}", chunk);
+
+            let mut decrypted = String::new();
+            cipher.decrypting_reader(&encrypted[..]).read_to_string(&mut decrypted).expect("read");
+            assert_eq!(TEXT, decrypted, "chunk size {}", chunk);
+        }
+    }
+
+    #[test]
+    fn test_text_ciphers() {
+        check(&Caesar::new(7).expect("valid key"));
+        check(&Caesar::with_alphabet(3, Alphabet::cyrillic()).expect("valid key"));
+        check(&Vigenere::new("randalthor").expect("valid key"));
+        check(&Vigenere::with_alphabet("s3cr3t", Alphabet::printable_ascii()).expect("valid key"));
+        check(&Vigenere::new("fortification").expect("valid key").with_mode(Mode::Beaufort));
+        check(&Vigenere::new("lemon").expect("valid key").with_mode(Mode::VariantBeaufort));
+        check(&Atbash::new());
+        check(&Autokey::new("queenly").expect("valid key"));
+        check(&Autokey::with_alphabet("ключ", Alphabet::cyrillic()).expect("valid key").with_feedback(Feedback::Ciphertext));
+        check(&Affine::with_alphabet(AffineKey::new(4, 7), Alphabet::cyrillic()).expect("valid key"));
+        check(&Rot13::new());
+        check(&Porta::new("fortification").expect("valid key"));
+        check(&Substitution::from_keyword("zebras"));
+        check(&Gronsfeld::new("31415").expect("valid key"));
+        check(&Trithemius::new(TrithemiusKey::new(3, 7)));
+        check(&RunningKey::new(&TEXT.repeat(2), 5).expect("valid key"));
+
+        // the running key runs out part way through the stream
+        let cipher = RunningKey::new("short key", 0).expect("valid key");
+        let mut writer = cipher.encrypting_writer(Vec::new());
+        assert!(writer.write_all(TEXT.as_bytes()).is_err());
+        assert_eq!(cipher.encrypt("Be normal, ").expect("long enough").as_bytes(), &writer.finish().expect("finish")[..]);
+    }
+
+    #[test]
+    fn test_one_time_pad() {
+        let data: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+        let pad = OneTimePad::generate(data.len());
+
+        let encrypted = write_chunked(pad.encrypting_writer(Vec::new()), &data, 4093).finish().expect("finish");
+        assert_eq!(crate::one_time_pad::encrypt(&data, pad.key()).expect("long enough"), encrypted);
+
+        let mut decrypted = Vec::new();
+        pad.decrypting_reader(&encrypted[..]).read_to_end(&mut decrypted).expect("read");
+        assert_eq!(data, decrypted);
+
+        let mut writer = pad.encrypting_writer(Vec::new());
+        writer.write_all(&data).expect("exactly the pad length");
+        assert!(writer.write_all(b"one byte too many").is_err());
+
+        // the pad is used up to its end before the overflow is reported
+        let mut writer = pad.encrypting_writer(Vec::new());
+        let mut too_long = data.clone();
+        too_long.push(0);
+        assert_eq!(data.len(), writer.write(&too_long).expect("short write"));
+        assert_eq!(encrypted, writer.finish().expect("finish"));
+    }
+
+    #[test]
+    fn test_foreign_letters() {
+        use crate::alphabet::ForeignCharPolicy;
+
+        let cipher = Vigenere::new("lemon").expect("valid key").with_policy(ForeignCharPolicy::Transliterate);
+        let encrypted = write_chunked(cipher.encrypting_writer(Vec::new()), "Straße".as_bytes(), 1).finish().expect("finish");
+        assert_eq!(cipher.encrypt("Straße").expect("encrypt").as_bytes(), &encrypted[..]);
+
+        let cipher = Caesar::new(3).expect("valid key").with_policy(ForeignCharPolicy::Reject);
+        let mut writer = cipher.encrypting_writer(Vec::new());
+        let err = writer.write_all("café".as_bytes()).expect_err("é is not an ascii letter");
+        assert_eq!(io::ErrorKind::InvalidData, err.kind());
+        // the letters before the failing one still go through
+        assert_eq!(b"fdi", &writer.finish().expect("finish")[..]);
+        let mut writer = cipher.encrypting_writer(Vec::new());
+        assert_eq!(3, writer.write("café".as_bytes()).expect("short write"));
+        assert!(writer.write("é".as_bytes()).is_err());
+
+        // also when the failing letter was split across chunks
+        let mut writer = cipher.encrypting_writer(Vec::new());
+        assert_eq!(3, writer.write(&"caé".as_bytes()[..3]).expect("half of é is held back"));
+        assert!(writer.write(&"é".as_bytes()[1..]).is_err());
+        // the held back half is still there, and ends the stream as a truncated character
+        assert_eq!(vec![b'f', b'd', 0xc3], writer.finish().expect("finish"));
+
+        let mut decrypted = Vec::new();
+        let err = cipher.decrypting_reader("fdié".as_bytes()).read_to_end(&mut decrypted).expect_err("é is not an ascii letter");
+        assert_eq!(io::ErrorKind::InvalidData, err.kind());
+        assert_eq!(b"caf", &decrypted[..]);
+    }
+
+    #[test]
+    fn test_invalid_utf8() {
+        let cipher = Caesar::new(1).expect("valid key");
+        let data = [b'a', 0xff, b'b', 0xe2, 0x82];
+        let encrypted = write_chunked(cipher.encrypting_writer(Vec::new()), &data, 1).finish().expect("finish");
+        assert_eq!(vec![b'b', 0xff, b'c', 0xe2, 0x82], encrypted);
+    }
+}
@@ -0,0 +1,278 @@
+use std::io;
+
+use rand::{CryptoRng, Rng, RngCore};
+use rand::seq::SliceRandom;
+
+use crate::alphabet::ASCII_LETTERS;
+use crate::candidate::Candidate;
+use crate::cipher::Cipher;
+use crate::detection::ngram::{Fitness, NgramScorer};
+use crate::error::{Error, Result};
+use crate::keygen::GenerateKey;
+use crate::stream::{CharTransform, StreamingCipher, TextTransform};
+
+const LETTERS: usize = 26;
+// english letters from most to least common, the solver's first guess
+const ENGLISH_BY_FREQUENCY: &str = "etaoinshrdlcumwfgypbvkjxqz";
+
+/// Encodes with `key`, a permutation of the 26 letters giving the replacement
+/// for `a`, `b`, ... in order. Case is kept, anything else passes through.
+pub fn encode(message: &str, key: &str) -> Result<String> {
+    let table = table(key)?;
+    Ok(handle(message, &table))
+}
+
+
+pub fn decode(message: &str, key: &str) -> Result<String> {
+    let table = invert(&table(key)?);
+    Ok(handle(message, &table))
+}
+
+
+/// Key built from a keyword: its distinct letters followed by the rest of the
+/// alphabet in order, so `"zebras"` gives `"zebrascdfghijklmnopqtuvwxy"`.
+pub fn key_from_keyword(keyword: &str) -> String {
+    let mut key = String::with_capacity(LETTERS);
+    let keyword = keyword.chars().filter(|ch| ch.is_ascii_alphabetic()).map(|ch| ch.to_ascii_lowercase());
+    for ch in keyword.chain('a'..='z') {
+        if !key.contains(ch) {
+            key.push(ch);
+        }
+    }
+    key
+}
+
+
+/// Recovers the key of an English message with [`crack_with_rng`], using the
+/// thread local generator and 10 restarts.
+pub fn crack(message: &str) -> Result<Candidate<String>> {
+    crack_with_rng(message, &mut rand::rng(), 10)
+}
+
+
+/// Hill-climbing solver: starting from a key guessed from letter frequencies,
+/// swaps pairs of letters in the key while that improves the quadgram fitness
+/// of the decryption, then starts over from `restarts` random keys and keeps
+/// the best result. A few hundred letters of ciphertext are usually enough.
+/// The score of the result is its quadgram fitness.
+pub fn crack_with_rng<R: Rng + ?Sized>(message: &str, rng: &mut R, restarts: usize) -> Result<Candidate<String>> {
+    let scorer = NgramScorer::english_quadgrams()?;
+    let letters = ASCII_LETTERS.indices(message);
+    if letters.len() < scorer.n() {
+        return Err(Error::InvalidArgument("message has too few letters to analyse".to_string()));
+    }
+
+    let mut best = climb(&letters, frequency_guess(&letters), scorer);
+    for _ in 0..restarts {
+        let mut start: Vec<usize> = (0..LETTERS).collect();
+        start.shuffle(rng);
+        let candidate = climb(&letters, start, scorer);
+        if candidate.1 > best.1 {
+            best = candidate;
+        }
+    }
+
+    // the solver works with the decryption table, the key is its inverse
+    let (decryption, fitness) = best;
+    let key: String = invert(&decryption).iter().map(|&i| ASCII_LETTERS.symbols()[i]).collect();
+    let decoded_message = handle(message, &decryption);
+    Ok(Candidate::new(key, decoded_message, fitness))
+}
+
+// maps the most common ciphertext letter to e, the next to t and so on
+fn frequency_guess(letters: &[usize]) -> Vec<usize> {
+    let mut counts = [0; LETTERS];
+    for &letter in letters {
+        counts[letter] += 1;
+    }
+    let mut by_frequency: Vec<usize> = (0..LETTERS).collect();
+    by_frequency.sort_by(|&a, &b| counts[b].cmp(&counts[a]));
+    let mut decryption = vec![0; LETTERS];
+    for (cipher, plain) in by_frequency.into_iter().zip(ENGLISH_BY_FREQUENCY.bytes()) {
+        decryption[cipher] = (plain - b'a') as usize;
+    }
+    decryption
+}
+
+// swaps pairs of the decryption table until no swap improves the fitness
+fn climb(letters: &[usize], mut decryption: Vec<usize>, scorer: &impl Fitness) -> (Vec<usize>, f64) {
+    let mut plain = vec![0; letters.len()];
+    let mut fitness = |decryption: &[usize]| {
+        for (plain, &letter) in plain.iter_mut().zip(letters) {
+            *plain = decryption[letter];
+        }
+        scorer.fitness(&plain)
+    };
+    let mut best = fitness(&decryption);
+    loop {
+        let mut improved = false;
+        for a in 0..LETTERS {
+            for b in a + 1..LETTERS {
+                decryption.swap(a, b);
+                let candidate = fitness(&decryption);
+                if candidate > best {
+                    best = candidate;
+                    improved = true;
+                } else {
+                    decryption.swap(a, b);
+                }
+            }
+        }
+        if !improved {
+            return (decryption, best);
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Substitution {
+    key: String,
+}
+
+impl Substitution {
+    pub fn new(key: &str) -> Result<Self> {
+        let key = key.to_ascii_lowercase();
+        Self::validate_key(&key)?;
+        Ok(Self { key })
+    }
+
+    pub fn from_keyword(keyword: &str) -> Self {
+        Self { key: key_from_keyword(keyword) }
+    }
+}
+
+impl Cipher for Substitution {
+    type Key = String;
+
+    fn validate_key(key: &String) -> Result<()> {
+        table(key).map(|_| ())
+    }
+
+    fn key(&self) -> &String {
+        &self.key
+    }
+
+    fn encrypt(&self, message: &str) -> Result<String> {
+        encode(message, &self.key)
+    }
+
+    fn decrypt(&self, message: &str) -> Result<String> {
+        decode(message, &self.key)
+    }
+}
+
+impl StreamingCipher for Substitution {
+    type Encryptor = TextTransform<SubstitutionStream>;
+    type Decryptor = TextTransform<SubstitutionStream>;
+
+    fn encryptor(&self) -> Self::Encryptor {
+        TextTransform::new(SubstitutionStream { table: table(&self.key).expect("validated key") })
+    }
+
+    fn decryptor(&self) -> Self::Decryptor {
+        TextTransform::new(SubstitutionStream { table: invert(&table(&self.key).expect("validated key")) })
+    }
+}
+
+pub struct SubstitutionStream {
+    table: Vec<usize>,
+}
+
+impl CharTransform for SubstitutionStream {
+    fn transform(&mut self, ch: char, output: &mut String) -> io::Result<()> {
+        output.push(substitute(ch, &self.table));
+        Ok(())
+    }
+}
+
+impl GenerateKey for Substitution {
+    fn generate_key<R: RngCore + CryptoRng + ?Sized>(rng: &mut R, _length: usize) -> String {
+        let mut key: Vec<char> = ('a'..='z').collect();
+        key.shuffle(rng);
+        key.into_iter().collect()
+    }
+}
+
+// position of the replacement of every letter
+fn table(key: &str) -> Result<Vec<usize>> {
+    let table = ASCII_LETTERS.indices(key);
+    let mut seen = [false; LETTERS];
+    for &i in &table {
+        seen[i] = true;
+    }
+    if key.chars().count() != LETTERS || table.len() != LETTERS || seen.contains(&false) {
+        return Err(Error::InvalidKey(format!("substitution key must be a permutation of the 26 letters, got {:?}", key)));
+    }
+    Ok(table)
+}
+
+fn invert(table: &[usize]) -> Vec<usize> {
+    let mut inverse = vec![0; table.len()];
+    for (from, &to) in table.iter().enumerate() {
+        inverse[to] = from;
+    }
+    inverse
+}
+
+fn substitute(ch: char, table: &[usize]) -> char {
+    match ASCII_LETTERS.index_of(ch) {
+        Some(i) => ASCII_LETTERS.symbol_like(table[i], ch),
+        None => ch,
+    }
+}
+
+fn handle(message: &str, table: &[usize]) -> String {
+    message.chars().
+        map(|ch| substitute(ch, table)).
+        collect()
+}
+
+
+#[cfg(test)]
+mod test {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    use super::*;
+
+    const KEY: &str = "phqgiumeaylnofdxjkrcvstzwb";
+
+    #[test]
+    fn test_encode() {
+        assert_eq!("Cei jvaql hkdtf udz", encode("The quick brown fox", KEY).expect("valid key"));
+        assert_eq!("The quick brown fox", decode("Cei jvaql hkdtf udz", KEY).expect("valid key"));
+        assert_eq!("Hello, 世界!", decode(&encode("Hello, 世界!", KEY).expect("valid key"), KEY).expect("valid key"));
+    }
+
+    #[test]
+    fn test_keys() {
+        assert_eq!("zebrascdfghijklmnopqtuvwxy", key_from_keyword("Zebras"));
+        assert_eq!("abcdefghijklmnopqrstuvwxyz", key_from_keyword(""));
+        assert_eq!("Siaa zq lkba", Substitution::from_keyword("zebras").encrypt("Flee at once").expect("encrypt"));
+
+        assert!(matches!(encode("text", "abc"), Err(Error::InvalidKey(_))));
+        assert!(matches!(encode("text", "aacdefghijklmnopqrstuvwxyz"), Err(Error::InvalidKey(_))));
+        assert!(Substitution::new("abcdefghijklmnopqrstuvwxyé").is_err());
+        assert_eq!(KEY, Substitution::new(&KEY.to_uppercase()).expect("valid key").key());
+
+        let key = Substitution::generate_key(&mut StdRng::seed_from_u64(1), 0);
+        assert!(Substitution::new(&key).is_ok());
+    }
+
+    #[test]
+    fn test_crack() {
+        let message = "On offering to help the blind man, the man who then stole his car, had not, at that precise moment,
+  had any evil intention, quite the contrary, what he did was nothing more than obey those feelings of generosity and altruism which,
+  as everyone knows, are the two best traits of human nature and to be found in much more hardened criminals than this one.";
+        let encoded = encode(message, KEY).expect("valid key");
+        let cracked = crack_with_rng(&encoded, &mut StdRng::seed_from_u64(3), 5).expect("long enough");
+        assert_eq!(message, cracked.decoded_message);
+        // letters missing from the message can end up anywhere in the key
+        let used: Vec<usize> = ASCII_LETTERS.indices(message);
+        for i in used {
+            assert_eq!(KEY.as_bytes()[i], cracked.key.as_bytes()[i]);
+        }
+
+        assert!(matches!(crack_with_rng("a b", &mut StdRng::seed_from_u64(3), 1), Err(Error::InvalidArgument(_))));
+    }
+}
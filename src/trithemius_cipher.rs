@@ -0,0 +1,237 @@
+use std::fmt;
+use std::io;
+
+use rand::{CryptoRng, Rng, RngCore};
+
+use crate::alphabet::{Alphabet, ASCII_LETTERS};
+use crate::candidate::{self, Candidate};
+use crate::cipher::Cipher;
+use crate::detection::ngram::{Fitness, NgramScorer};
+use crate::error::{Error, Result};
+use crate::keygen::GenerateKey;
+use crate::stream::{CharTransform, StreamingCipher, TextTransform};
+
+/// Progressive shift: the first letter moves by `start`, every following
+/// letter by `step` more than the one before. The classic tabula recta of
+/// Trithemius is start 0, step 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TrithemiusKey {
+    pub start: usize,
+    pub step: usize,
+}
+
+impl TrithemiusKey {
+    pub fn new(start: usize, step: usize) -> Self {
+        Self { start, step }
+    }
+
+    pub fn classic() -> Self {
+        Self::new(0, 1)
+    }
+
+    // shift of letter number `position`, reduced modulo `size`
+    fn shift(&self, position: usize, size: usize) -> usize {
+        (self.start % size + (position % size) * (self.step % size)) % size
+    }
+}
+
+impl Default for TrithemiusKey {
+    fn default() -> Self {
+        Self::classic()
+    }
+}
+
+impl fmt::Display for TrithemiusKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{},{}", self.start, self.step)
+    }
+}
+
+pub fn encode(message: &str, key: TrithemiusKey) -> String {
+    handle(message, key, &ASCII_LETTERS, true)
+}
+
+
+pub fn decode(message: &str, key: TrithemiusKey) -> String {
+    handle(message, key, &ASCII_LETTERS, false)
+}
+
+
+pub fn encode_with(message: &str, key: TrithemiusKey, alphabet: &Alphabet) -> String {
+    handle(message, key, alphabet, true)
+}
+
+
+pub fn decode_with(message: &str, key: TrithemiusKey, alphabet: &Alphabet) -> String {
+    handle(message, key, alphabet, false)
+}
+
+
+/// Tries all 676 start and step pairs (step 0 being a plain Caesar shift) and
+/// ranks the decryptions by n-gram fitness, best first.
+pub fn crack(message: &str) -> Result<Vec<Candidate<TrithemiusKey>>> {
+    let letters = ASCII_LETTERS.indices(message);
+    if letters.is_empty() {
+        return Err(Error::InvalidArgument("message has no letters to analyse".to_string()));
+    }
+    // very short messages are scored on shorter n-grams
+    let scorer = NgramScorer::english(letters.len().min(4))?;
+    let size = ASCII_LETTERS.len();
+
+    let mut plain = vec![0; letters.len()];
+    let mut candidates = Vec::with_capacity(size * size);
+    for step in 0..size {
+        for start in 0..size {
+            let key = TrithemiusKey::new(start, step);
+            for (position, (plain, &letter)) in plain.iter_mut().zip(&letters).enumerate() {
+                *plain = (letter + size - key.shift(position, size)) % size;
+            }
+            let score = scorer.fitness(&plain);
+            candidates.push(Candidate::new(key, decode(message, key), score));
+        }
+    }
+    candidate::rank(&mut candidates);
+    Ok(candidates)
+}
+
+/// Trithemius cipher, a Caesar shift that grows with every letter. Characters
+/// outside the alphabet pass through and do not advance the shift.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trithemius {
+    key: TrithemiusKey,
+    alphabet: Alphabet,
+}
+
+impl Trithemius {
+    pub fn new(key: TrithemiusKey) -> Self {
+        Self::with_alphabet(key, ASCII_LETTERS.clone())
+    }
+
+    pub fn with_alphabet(key: TrithemiusKey, alphabet: Alphabet) -> Self {
+        Self { key, alphabet }
+    }
+}
+
+impl Cipher for Trithemius {
+    type Key = TrithemiusKey;
+
+    fn validate_key(_key: &TrithemiusKey) -> Result<()> {
+        // start and step are reduced modulo the alphabet length, so any pair is usable
+        Ok(())
+    }
+
+    fn key(&self) -> &TrithemiusKey {
+        &self.key
+    }
+
+    fn encrypt(&self, message: &str) -> Result<String> {
+        Ok(encode_with(message, self.key, &self.alphabet))
+    }
+
+    fn decrypt(&self, message: &str) -> Result<String> {
+        Ok(decode_with(message, self.key, &self.alphabet))
+    }
+}
+
+impl StreamingCipher for Trithemius {
+    type Encryptor = TextTransform<TrithemiusStream>;
+    type Decryptor = TextTransform<TrithemiusStream>;
+
+    fn encryptor(&self) -> Self::Encryptor {
+        TextTransform::new(TrithemiusStream::new(self.key, self.alphabet.clone(), true))
+    }
+
+    fn decryptor(&self) -> Self::Decryptor {
+        TextTransform::new(TrithemiusStream::new(self.key, self.alphabet.clone(), false))
+    }
+}
+
+/// Character by character form of the Trithemius cipher that counts the
+/// letters seen so far, used by the streaming adapters.
+pub struct TrithemiusStream {
+    key: TrithemiusKey,
+    position: usize,
+    alphabet: Alphabet,
+    encode: bool,
+}
+
+impl TrithemiusStream {
+    fn new(key: TrithemiusKey, alphabet: Alphabet, encode: bool) -> Self {
+        Self { key, position: 0, alphabet, encode }
+    }
+}
+
+impl CharTransform for TrithemiusStream {
+    fn transform(&mut self, ch: char, output: &mut String) -> io::Result<()> {
+        if !self.alphabet.contains(ch) {
+            output.push(ch);
+            return Ok(());
+        }
+        let shift = self.key.shift(self.position, self.alphabet.len());
+        output.push(self.alphabet.shift(ch, shift, self.encode).unwrap_or(ch));
+        self.position += 1;
+        Ok(())
+    }
+}
+
+impl GenerateKey for Trithemius {
+    // a key for the ASCII letters of `Trithemius::new`; a step of 0 would make it a plain caesar shift
+    fn generate_key<R: RngCore + CryptoRng + ?Sized>(rng: &mut R, _length: usize) -> TrithemiusKey {
+        let size = ASCII_LETTERS.len();
+        TrithemiusKey::new(rng.random_range(0..size), rng.random_range(1..size))
+    }
+}
+
+fn handle(message: &str, key: TrithemiusKey, alphabet: &Alphabet, encode: bool) -> String {
+    let mut position = 0;
+    message.chars().
+        map(|ch| match alphabet.contains(ch) {
+            true => {
+                let shifted = alphabet.shift(ch, key.shift(position, alphabet.len()), encode).unwrap_or(ch);
+                position += 1;
+                shifted
+            }
+            false => ch,
+        }).
+        collect()
+}
+
+
+#[cfg(test)]
+mod test {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+
+    #[test]
+    fn test_encode() {
+        assert_eq!("abcdefg", encode("aaaaaaa", TrithemiusKey::classic()));
+        assert_eq!("Tig tynir", encode("The quick", TrithemiusKey::classic()));
+        assert_eq!("The quick", decode("Tig tynir", TrithemiusKey::classic()));
+        assert_eq!("dgjmp", encode("aaaaa", TrithemiusKey::new(3, 3)));
+        assert_eq!(encode("aaaaa", TrithemiusKey::new(3, 3)), encode("aaaaa", TrithemiusKey::new(29, 55)));
+    }
+
+    #[test]
+    fn test_crack() {
+        let message = "Be normal, and the crowd will accept you. Be deranged, and they will make you their leader.";
+        for key in [TrithemiusKey::classic(), TrithemiusKey::new(7, 3), TrithemiusKey::new(12, 0)] {
+            let candidates = crack(&encode(message, key)).expect("has letters");
+            assert_eq!(676, candidates.len());
+            assert_eq!(key, candidates[0].key);
+            assert_eq!(message, candidates[0].decoded_message);
+        }
+        assert!(matches!(crack("1984"), Err(Error::InvalidArgument(_))));
+    }
+
+    #[test]
+    fn test_cipher_trait() {
+        let key = Trithemius::generate_key(&mut StdRng::seed_from_u64(24), 0);
+        assert_ne!(0, key.step);
+        let cipher = Trithemius::with_alphabet(key, Alphabet::greek());
+        assert_eq!(key, *cipher.key());
+        let message = "Καλημέρα κόσμε";
+        assert_eq!(message, cipher.decrypt(&cipher.encrypt(message).expect("encrypt")).expect("decrypt"));
+    }
+}
@@ -1,19 +1,99 @@
+use std::io;
+
+use rand::{CryptoRng, RngCore};
+
+use crate::alphabet::{Alphabet, ForeignCharPolicy, ASCII_LETTERS};
+use crate::cipher::Cipher;
+use crate::detection::frequency::LanguageProfile;
+use crate::detection::key_length;
+use crate::detection::ngram::{Fitness, NgramScorer};
+use crate::error::{Error, Result};
+use crate::keygen::GenerateKey;
+use crate::stream::{CharTransform, StreamingCipher, TextTransform};
+
+/// Tableau of the keyed polyalphabetic engine, with `p` the plaintext, `k`
+/// the key and `c` the ciphertext letter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Mode {
+    /// `c = p + k`
+    #[default]
+    Vigenere,
+    /// `c = k - p`, reciprocal: encryption and decryption are the same
+    Beaufort,
+    /// `c = p - k`, Vigenere decryption used to encrypt
+    VariantBeaufort,
+}
+
+impl Mode {
+    // `k` is the position of the key letter in `alphabet`, `ch` a symbol of it
+    fn apply(self, ch: char, k: usize, alphabet: &Alphabet, encode: bool) -> char {
+        match self {
+            Mode::Vigenere => alphabet.shift(ch, k, encode),
+            Mode::VariantBeaufort => alphabet.shift(ch, k, !encode),
+            Mode::Beaufort => alphabet.index_of(ch).
+                map(|p| alphabet.symbol_like((k % alphabet.len() + alphabet.len() - p) % alphabet.len(), ch)),
+        }.unwrap_or(ch)
+    }
+}
+
 pub fn encode(message: &str, key: &str) -> String {
-    handle(message, key, true)
+    handle(message, key, &ASCII_LETTERS, Mode::Vigenere, true)
 }
 
 
 pub fn decode(message: &str, key: &str) -> String {
-    handle(message, key, false)
+    handle(message, key, &ASCII_LETTERS, Mode::Vigenere, false)
+}
+
+
+pub fn encode_with(message: &str, key: &str, alphabet: &Alphabet) -> String {
+    handle(message, key, alphabet, Mode::Vigenere, true)
+}
+
+
+pub fn decode_with(message: &str, key: &str, alphabet: &Alphabet) -> String {
+    handle(message, key, alphabet, Mode::Vigenere, false)
+}
+
+
+/// Like [`encode_with`] with the tableau of `mode`.
+pub fn encode_with_mode(message: &str, key: &str, alphabet: &Alphabet, mode: Mode) -> String {
+    handle(message, key, alphabet, mode, true)
+}
+
+
+/// Like [`decode_with`] with the tableau of `mode`.
+pub fn decode_with_mode(message: &str, key: &str, alphabet: &Alphabet, mode: Mode) -> String {
+    handle(message, key, alphabet, mode, false)
+}
+
+
+/// Like [`encode_with`], applying `policy` to letters that are not part of `alphabet`.
+pub fn encode_with_policy(message: &str, key: &str, alphabet: &Alphabet, policy: ForeignCharPolicy) -> Result<String> {
+    let message = policy.prepare(message, alphabet)?;
+    Ok(handle(&message, key, alphabet, Mode::Vigenere, true))
+}
+
+
+/// Like [`decode_with`], applying `policy` to letters that are not part of `alphabet`.
+pub fn decode_with_policy(message: &str, key: &str, alphabet: &Alphabet, policy: ForeignCharPolicy) -> Result<String> {
+    let message = policy.prepare(message, alphabet)?;
+    Ok(handle(&message, key, alphabet, Mode::Vigenere, false))
 }
 
 #[derive(Debug, PartialEq)]
 pub struct BruteForceResponse {
-    decoded_message: String,
-    key: String,
+    pub decoded_message: String,
+    pub key: String,
+}
+
+pub fn decode_brute_force(message: &str, limit: usize) -> Result<BruteForceResponse> {
+    brute_force(message, limit, Mode::Vigenere)
 }
 
-pub fn decode_brute_force(message: &str, limit: usize) -> anyhow::Result<BruteForceResponse> {
+/// Tries the first `limit` dictionary words as keys under the tableau of
+/// `mode`, returning the first decryption that reads as English.
+pub(crate) fn brute_force(message: &str, limit: usize, mode: Mode) -> Result<BruteForceResponse> {
     let detection = crate::detection::detect_english::Detector::new_with_fix_db()?;
     let iter = detection.iter_dictionary_words().
         filter(|s| {
@@ -22,39 +102,267 @@ pub fn decode_brute_force(message: &str, limit: usize) -> anyhow::Result<BruteFo
         take(limit);
 
     for possible_key in iter {
-        if possible_key.len() == 0 {
+        if possible_key.is_empty() {
             continue;
         }
-        let plain_text = decode(message, &possible_key);
+        let plain_text = handle(message, &possible_key, &ASCII_LETTERS, mode, false);
         if detection.is_english(&plain_text) {
             return Ok(BruteForceResponse { decoded_message: plain_text, key: possible_key });
         }
     }
-    anyhow::bail!("message could not be decoded")
+    Err(Error::ExhaustedSearch)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatisticalResponse {
+    pub decoded_message: String,
+    pub key: String,
+}
+
+/// Recovers an arbitrary key (not just a dictionary word) from an English
+/// message: estimates the key length, solves every column as a Caesar shift
+/// by letter frequencies, then refines the key letter by letter with
+/// quadgram fitness. Needs a few hundred letters to be reliable.
+pub fn decode_statistical(message: &str, max_key_length: usize) -> Result<StatisticalResponse> {
+    let key: String = statistical_key(message, max_key_length, 26, unshift)?.iter().
+        map(|&shift| ASCII_LETTERS.symbols()[shift]).
+        collect();
+    Ok(StatisticalResponse { decoded_message: decode(message, &key), key })
+}
+
+// a plain letter from a ciphertext letter and the key letter (0 to 25) that shifted it
+pub(crate) fn unshift(letter: usize, shift: usize) -> usize {
+    (letter + 26 - shift) % 26
+}
+
+// The periodic key of an English message over the ASCII letters, where every
+// key letter is one of `0..keys` and `decrypt(letter, key)` is the plain
+// letter: the three likeliest key lengths are solved and refined, the best
+// key is returned reduced to its shortest period.
+pub(crate) fn statistical_key(message: &str, max_key_length: usize, keys: usize, decrypt: impl Fn(usize, usize) -> usize + Copy) -> Result<Vec<usize>> {
+    let profile = LanguageProfile::english();
+    let scorer = NgramScorer::english_quadgrams()?;
+    let letters = ASCII_LETTERS.indices(message);
+    let max_key_length = max_key_length.min(letters.len() / 2);
+    let lengths = key_length::estimate(message, &profile, max_key_length)?;
+
+    let mut best: Option<(Vec<usize>, f64)> = None;
+    for candidate in lengths.iter().take(3) {
+        let mut key = solve_columns(&letters, candidate.length, &profile, keys, decrypt);
+        let fitness = refine(&letters, &mut key, scorer, keys, decrypt);
+        // a multiple of the key length fits just as well, keep the shorter key on ties
+        if best.as_ref().is_none_or(|(_, best)| fitness > *best) {
+            best = Some((key, fitness));
+        }
+    }
+    let (key, _) = best.ok_or(Error::ExhaustedSearch)?;
+    Ok(shortest_period(&key).to_vec())
+}
+
+// the key of every column, out of `0..keys`, whose decryption is closest to the language by
+// chi-squared; `decrypt` must map the letters of the alphabet one to one
+pub(crate) fn solve_columns(letters: &[usize], length: usize, profile: &LanguageProfile, keys: usize, decrypt: impl Fn(usize, usize) -> usize) -> Vec<usize> {
+    let size = profile.alphabet().len();
+    (0..length).
+        map(|column| {
+            let mut counts = vec![0; size];
+            for &letter in letters.iter().skip(column).step_by(length) {
+                counts[letter] += 1;
+            }
+            let mut plain = vec![0; size];
+            (0..keys).
+                min_by(|&a, &b| {
+                    let mut chi = |key: usize| {
+                        for (letter, count) in counts.iter().enumerate() {
+                            plain[decrypt(letter, key)] = *count;
+                        }
+                        profile.chi_squared_counts(&plain)
+                    };
+                    chi(a).total_cmp(&chi(b))
+                }).
+                expect("at least one key")
+        }).
+        collect()
+}
+
+// sets each key letter in turn to the key (out of `0..keys`) with the best fitness
+// until nothing changes, returns the final fitness
+pub(crate) fn refine(letters: &[usize], key: &mut [usize], scorer: &impl Fitness, keys: usize, decrypt: impl Fn(usize, usize) -> usize) -> f64 {
+    let mut plain = vec![0; letters.len()];
+    let mut fitness = |key: &[usize]| {
+        for (i, (&letter, plain)) in letters.iter().zip(plain.iter_mut()).enumerate() {
+            *plain = decrypt(letter, key[i % key.len()]);
+        }
+        scorer.fitness(&plain)
+    };
+    let mut best = fitness(key);
+    loop {
+        let mut improved = false;
+        for position in 0..key.len() {
+            for value in 0..keys {
+                let current = key[position];
+                key[position] = value;
+                let candidate = fitness(key);
+                if candidate > best {
+                    best = candidate;
+                    improved = true;
+                } else {
+                    key[position] = current;
+                }
+            }
+        }
+        if !improved {
+            return best;
+        }
+    }
+}
+
+// "lemonlemon" -> "lemon"
+pub(crate) fn shortest_period(key: &[usize]) -> &[usize] {
+    (1..key.len()).
+        find(|&period| key.len().is_multiple_of(period) && key.chunks(period).all(|chunk| chunk == &key[..period])).
+        map_or(key, |period| &key[..period])
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Vigenere {
+    key: String,
+    alphabet: Alphabet,
+    policy: ForeignCharPolicy,
+    mode: Mode,
+}
+
+impl Vigenere {
+    pub fn new(key: &str) -> Result<Self> {
+        let key = key.to_string();
+        Self::validate_key(&key)?;
+        Ok(Self { key, alphabet: ASCII_LETTERS.clone(), policy: ForeignCharPolicy::default(), mode: Mode::default() })
+    }
+
+    pub fn with_alphabet(key: &str, alphabet: Alphabet) -> Result<Self> {
+        if !key.chars().any(|ch| alphabet.contains(ch)) {
+            return Err(Error::InvalidKey("vigenere key must contain at least one symbol of the alphabet".to_string()));
+        }
+        Ok(Self { key: key.to_string(), alphabet, policy: ForeignCharPolicy::default(), mode: Mode::default() })
+    }
+
+    pub fn with_policy(mut self, policy: ForeignCharPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Switches the tableau, e.g. to the Beaufort cipher.
+    pub fn with_mode(mut self, mode: Mode) -> Self {
+        self.mode = mode;
+        self
+    }
+}
+
+impl Cipher for Vigenere {
+    type Key = String;
+
+    fn validate_key(key: &String) -> Result<()> {
+        if !key.chars().any(|ch| ch.is_ascii_alphabetic()) {
+            return Err(Error::InvalidKey("vigenere key must contain at least one ascii letter".to_string()));
+        }
+        Ok(())
+    }
+
+    fn key(&self) -> &String {
+        &self.key
+    }
+
+    fn encrypt(&self, message: &str) -> Result<String> {
+        let message = self.policy.prepare(message, &self.alphabet)?;
+        Ok(handle(&message, &self.key, &self.alphabet, self.mode, true))
+    }
+
+    fn decrypt(&self, message: &str) -> Result<String> {
+        let message = self.policy.prepare(message, &self.alphabet)?;
+        Ok(handle(&message, &self.key, &self.alphabet, self.mode, false))
+    }
+}
+
+impl StreamingCipher for Vigenere {
+    type Encryptor = TextTransform<VigenereStream>;
+    type Decryptor = TextTransform<VigenereStream>;
+
+    fn encryptor(&self) -> Self::Encryptor {
+        TextTransform::new(VigenereStream::new(&self.key, self.alphabet.clone(), self.policy, true).expect("validated key").with_mode(self.mode))
+    }
+
+    fn decryptor(&self) -> Self::Decryptor {
+        TextTransform::new(VigenereStream::new(&self.key, self.alphabet.clone(), self.policy, false).expect("validated key").with_mode(self.mode))
+    }
+}
+
+/// Character by character form of the Vigenere cipher that remembers its
+/// position in the key, used by the streaming adapters.
+pub struct VigenereStream {
+    key: Vec<char>,
+    next_key: usize,
+    alphabet: Alphabet,
+    policy: ForeignCharPolicy,
+    mode: Mode,
+    encode: bool,
+    position: usize,
+}
+
+impl VigenereStream {
+    pub fn new(key: &str, alphabet: Alphabet, policy: ForeignCharPolicy, encode: bool) -> Result<Self> {
+        if !key.chars().any(|ch| alphabet.contains(ch)) {
+            return Err(Error::InvalidKey("vigenere key must contain at least one symbol of the alphabet".to_string()));
+        }
+        Ok(Self { key: key.chars().collect(), next_key: 0, alphabet, policy, mode: Mode::default(), encode, position: 0 })
+    }
+
+    pub fn with_mode(mut self, mode: Mode) -> Self {
+        self.mode = mode;
+        self
+    }
+}
+
+impl CharTransform for VigenereStream {
+    fn transform(&mut self, ch: char, output: &mut String) -> io::Result<()> {
+        let Self { key, next_key, alphabet, policy, mode, encode, position } = self;
+        policy.prepare_char(ch, *position, alphabet, |ch| {
+            if !alphabet.contains(ch) {
+                output.push(ch);
+                return;
+            }
+            let k = key[*next_key % key.len()];
+            *next_key += 1;
+            let shifted = match alphabet.index_of(k) {
+                Some(k) => mode.apply(ch, k, alphabet, *encode),
+                None => ch,
+            };
+            output.push(shifted);
+        }).map_err(io::Error::from)?;
+        *position += 1;
+        Ok(())
+    }
 }
 
-fn handle(message: &str, key: &str, encode: bool) -> String {
+impl GenerateKey for Vigenere {
+    fn generate_key<R: RngCore + CryptoRng + ?Sized>(rng: &mut R, length: usize) -> String {
+        crate::keygen::random_symbols(rng, &ASCII_LETTERS, length.max(1))
+    }
+}
+
+fn handle(message: &str, key: &str, alphabet: &Alphabet, mode: Mode, encode: bool) -> String {
     let mut key = key.chars().
         filter(|x| !x.is_whitespace() || !x.is_ascii_alphanumeric()).
         cycle();
 
     message.chars()
         .map(|ch| {
-            if ch.is_alphabetic() {
+            if alphabet.contains(ch) {
                 let k = key.next().expect("next letter in key failed");
-
-                if k.to_ascii_lowercase() as usize > u8::MAX as usize {
-                    return ch;
+                // key characters outside of the alphabet leave the message character untouched
+                match alphabet.index_of(k) {
+                    Some(k) => mode.apply(ch, k, alphabet, encode),
+                    None => ch,
                 }
-                if (k.to_ascii_lowercase() as isize - 'a' as isize) < 0 {
-                    return ch;
-                }
-
-                let k = k.to_ascii_lowercase() as u8 - 'a' as u8;
-                let k = if encode { k } else { 26 - k };
-                let base = if ch.is_ascii_uppercase() { 'A' as u8 } else { 'a' as u8 };
-                let ch = ch as u8;
-                (base + (ch - base + k) % 26) as char
             } else {
                 ch
             }
@@ -64,7 +372,11 @@ fn handle(message: &str, key: &str, encode: bool) -> String {
 
 #[cfg(test)]
 mod test {
+    use rand::{Rng, SeedableRng};
+    use rand::rngs::StdRng;
+
     use super::*;
+    use crate::alphabet::ForeignCharError;
 
     #[test]
     fn test_encode() {
@@ -84,4 +396,88 @@ mod test {
             key: "love".to_string(),
         }, decode_brute_force("Ms iscavp, lby xss xvzky atzg enqzte mjy. Ms yicoikpr, vro hcij kdpw avop mjy evzmc zzeosm -Asszp Zt Omxs", usize::MAX).expect("should be able to decode simple key"));
     }
+
+    #[test]
+    fn test_decode_statistical() {
+        let message = "On offering to help the blind man, the man who then stole his car, had not, at that precise moment,
+  had any evil intention, quite the contrary, what he did was nothing more than obey those feelings of generosity and altruism which,
+  as everyone knows, are the two best traits of human nature and to be found in much more hardened criminals than this one,
+  a simple car-thief without any hope of advancing in his profession, exploited by the real owners of this enterprise,
+  for it is they who take advantage of the needs of the poor.";
+        for key in ["xqzjr", "lemon", "qwfpgjluyz", "b"] {
+            let response = decode_statistical(&encode(message, key), 20).expect("long enough");
+            assert_eq!(key, response.key);
+            assert_eq!(message, response.decoded_message);
+        }
+        assert!(matches!(decode_statistical("1984", 20), Err(Error::InvalidArgument(_))));
+    }
+
+    #[test]
+    fn test_alphabets() {
+        let greek = Alphabet::greek();
+        assert_eq!("Βδζ", encode_with("Αβγ", "βγδ", &greek));
+        assert_eq!("Αβγ", decode_with("Βδζ", "βγδ", &greek));
+
+        let printable = Alphabet::printable_ascii();
+        let encoded = encode_with("Meet me at 10:00!", "s3cr3t", &printable);
+        assert!(!encoded.contains(' '));
+        assert_eq!("Meet me at 10:00!", decode_with(&encoded, "s3cr3t", &printable));
+
+        assert!(Vigenere::with_alphabet("abc", Alphabet::cyrillic()).is_err());
+        let cipher = Vigenere::with_alphabet("ключ", Alphabet::cyrillic()).expect("valid key");
+        let encrypted = cipher.encrypt("Съешь же ещё этих мягких французских булок").expect("encrypt");
+        assert_eq!("Съешь же ещё этих мягких французских булок", cipher.decrypt(&encrypted).expect("decrypt"));
+    }
+
+    #[test]
+    fn test_foreign_letters() {
+        let letters = Alphabet::ascii_letters();
+        assert_eq!("Dxdoßr", encode("Straße", "lemon"));
+        assert_eq!("Straße", decode(&encode("Straße", "lemon"), "lemon"));
+        assert!(matches!(
+            encode_with_policy("café au lait", "lemon", &letters, ForeignCharPolicy::Reject),
+            Err(Error::ForeignCharacter(ForeignCharError { ch: 'é', position: 3 }))
+        ));
+        let encoded = encode_with_policy("Straße", "lemon", &letters, ForeignCharPolicy::Transliterate).expect("transliterate");
+        assert_eq!("Strasse", decode(&encoded, "lemon"));
+    }
+
+    #[test]
+    fn test_round_trip_arbitrary_unicode() {
+        let mut rng = StdRng::seed_from_u64(11);
+        let alphabets = [Alphabet::ascii_letters(), Alphabet::latin(), Alphabet::greek(), Alphabet::alphanumeric()];
+        for _ in 0..200 {
+            let message = crate::alphabet::random_unicode(&mut rng, 40);
+            let key: String = (0..rng.random_range(1..8)).map(|_| rng.random_range('a'..='z')).collect();
+            for alphabet in &alphabets {
+                let pass_through = ForeignCharPolicy::PassThrough;
+                let encoded = encode_with_policy(&message, &key, alphabet, pass_through).expect("never fails");
+                assert_eq!(message, decode_with_policy(&encoded, &key, alphabet, pass_through).expect("never fails"));
+
+                let transliterate = ForeignCharPolicy::Transliterate;
+                let expected = transliterate.prepare(&message, alphabet).expect("never fails");
+                let encoded = encode_with_policy(&message, &key, alphabet, transliterate).expect("never fails");
+                assert_eq!(expected, decode_with_policy(&encoded, &key, alphabet, transliterate).expect("never fails"));
+
+                let reject = ForeignCharPolicy::Reject;
+                if let Ok(encoded) = encode_with_policy(&message, &key, alphabet, reject) {
+                    assert_eq!(message, decode_with_policy(&encoded, &key, alphabet, reject).expect("same letters"));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_cipher_trait() {
+        assert!(Vigenere::new("").is_err());
+        assert!(Vigenere::new("1234").is_err());
+
+        let cipher = Vigenere::new("randalthor").expect("valid key");
+        assert_eq!("randalthor", cipher.key());
+        assert_eq!("Se arrxts, oeu tuh cchdr", cipher.encrypt("Be normal, and the crowd").expect("encrypt"));
+        assert_eq!("Be normal, and the crowd", cipher.decrypt("Se arrxts, oeu tuh cchdr").expect("decrypt"));
+
+        assert!(matches!(VigenereStream::new("", ASCII_LETTERS.clone(), ForeignCharPolicy::default(), true), Err(Error::InvalidKey(_))));
+        assert!(matches!(VigenereStream::new("1234", ASCII_LETTERS.clone(), ForeignCharPolicy::default(), true), Err(Error::InvalidKey(_))));
+    }
 }